@@ -1,5 +1,5 @@
-use std::{collections::HashMap, fs::File, io::Write, ops::DerefMut, sync::{Mutex, MutexGuard}};
-use arcdps::{callbacks::{ImguiCallback, OptionsWindowsCallback}, exports, extras::{ExtrasAddonInfo, UserInfoIter}, imgui::{ColorEdit, Io, TableColumnSetup, Ui}};
+use std::{collections::{HashMap, HashSet, VecDeque}, fs::File, io::{ErrorKind, Write}, ops::DerefMut, path::Path, sync::{Mutex, MutexGuard, atomic::{AtomicBool, AtomicU64, Ordering}}, thread::JoinHandle, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use arcdps::{Agent, CombatEvent, callbacks::{ImguiCallback, OptionsWindowsCallback}, exports, extras::{ChatMessageInfo, ExtrasAddonInfo, UserInfoIter}, imgui::{ColorEdit, Condition, InputTextCallbackHandler, InputTextMultilineCallback, Io, Key, MouseButton, Selectable, StyleColor, TableBgTarget, TableColumnFlags, TableColumnSetup, TableFlags, TableSortDirection, TextCallbackData, TreeNodeFlags, Ui, WindowFocusedFlags}};
 use once_cell::sync::Lazy;
 use toml::{map::Map, Value};
 use windows::System::VirtualKey;
@@ -12,9 +12,10 @@ arcdps::export! {
     release,
     imgui: draw_window,
     extras_squad_update: squad_update,
+    extras_chat_message: chat_message,
+    combat,
     options_windows: options,
     options_end: options_tab,
-    wnd_filter: shortcuts,
     wnd_nofilter: nofilter,
 }
 
@@ -23,15 +24,227 @@ struct Player {
     lowercase_name: String,
     comment: String,
     lowercase_comment: String,
-    in_squad: bool
+    in_squad: bool,
+    /// Contexts this player has been seen in (e.g. "raid", "WvW")
+    contexts: Vec<String>,
+    /// Freeform labels (e.g. "tank", "friend", "guild")
+    labels: Vec<String>,
+    /// Last time the player was seen in the squad, in unix seconds
+    last_seen: Option<i64>,
+    /// When the player was added to the list, in unix seconds. None for players from older configs
+    first_met: Option<i64>,
+    /// How many times the player has joined the squad
+    encounter_count: u32,
+    /// Custom name color, used instead of the default and inactive colors
+    color: Option<[f32;4]>,
+    /// Last known elite specialization, or profession if it's a core build
+    profession: Option<String>,
+    /// Subgroup while in the squad, 0 being the first one
+    subgroup: Option<u8>,
+    /// Squad leader of the current squad
+    is_commander: bool,
+    /// Shown at the top of the table, even when out of the squad
+    pinned: bool,
+    /// Added from the UI or an import, instead of by joining the squad
+    added_manually: bool,
+    /// 1 to 5 stars, 0 being unrated
+    rating: u8,
+    /// Someone to avoid. Shown in IGNORED_COLOR at the bottom of the table, or hidden
+    ignored: bool,
+    /// When the player last left the squad. Not saved
+    left_at: Option<Instant>,
+    /// When the player joined the squad they are currently in. Not saved
+    joined_at: Option<Instant>,
+    /// When the player last wrote in the squad chat. Not saved
+    last_chat: Option<Instant>,
 }
 
 impl Player {
+    fn new(username: &str, comment: String, added_manually: bool) -> Player {
+        Player {
+            name: username.to_string(),
+            lowercase_name: username.to_lowercase(),
+            lowercase_comment: comment.to_lowercase(),
+            comment,
+            in_squad: false,
+            contexts: Vec::new(),
+            labels: Vec::new(),
+            last_seen: None,
+            first_met: Some(unix_now()),
+            encounter_count: 0,
+            color: None,
+            profession: None,
+            subgroup: None,
+            is_commander: false,
+            pinned: false,
+            added_manually,
+            rating: 0,
+            ignored: false,
+            left_at: None,
+            joined_at: None,
+            last_chat: None,
+        }
+    }
+
+    /// Multi-line summary of everything known about this player. Empty fields are skipped
+    fn details(&self) -> String {
+        let mut lines = vec![self.name.clone()];
+        if self.in_squad {
+            match self.joined_at {
+                Some(joined_at) => lines.push(format!("In squad for {}", format_duration(joined_at.elapsed()))),
+                None => lines.push("In squad".to_string()),
+            }
+        } else if let Some(last_seen) = self.last_seen {
+            lines.push(format!("Last seen {}", format_time_ago(last_seen)));
+        }
+        if let Some(profession) = &self.profession {
+            lines.push(profession.clone());
+        }
+        if self.encounter_count != 0 {
+            lines.push(format!("Encounters: {}", self.encounter_count));
+        }
+        if self.rating != 0 {
+            lines.push(format!("Rating: {}/{MAX_RATING}", self.rating));
+        }
+        if let Some(first_met) = self.first_met {
+            lines.push(format!("First met {}", format_time_ago(first_met)));
+        }
+        if self.added_manually {
+            lines.push("Added by hand".to_string());
+        }
+        if !self.contexts.is_empty() {
+            lines.push(format!("Seen in: {}", self.contexts.join(", ")));
+        }
+        if !self.labels.is_empty() {
+            lines.push(format!("Labels: {}", self.labels.join(", ")));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Whether there's nothing worth saving about this player
+    fn is_blank(&self) -> bool {
+        self.comment.is_empty() && !self.pinned && !self.ignored
+            && self.rating == 0 && self.labels.is_empty() && self.color.is_none()
+    }
+
+    fn leave(&mut self) {
+        if self.in_squad {
+            self.left_at = Some(Instant::now());
+            self.last_seen = Some(unix_now());
+        }
+        self.in_squad = false;
+        self.joined_at = None;
+        self.subgroup = None;
+        self.is_commander = false;
+    }
+
+    fn add_label(&mut self, label: &str) {
+        let label = label.trim();
+        if !label.is_empty() && !self.labels.iter().any(|l| l == label) {
+            self.labels.push(label.to_string())
+        }
+    }
+
+    /// Adds a line with `note`, starting with the date of `timestamp` (in unix seconds), at the end of the comment
+    fn add_dated_note(&mut self, note: &str, timestamp: i64) {
+        let note = note.trim();
+        if note.is_empty() {
+            return
+        }
+        if !self.comment.is_empty() {
+            self.comment.push('\n');
+        }
+        self.comment.push_str(&format_date(timestamp, "[{year}-{month}-{day}] "));
+        self.comment.push_str(note);
+        self.lowercase_comment = self.comment.to_lowercase();
+    }
+
+    /// Updates lowercase_comment after editing the comment directly
+    fn sync_lowercase_comment(&mut self) {
+        self.lowercase_comment = self.comment.to_lowercase();
+    }
+
+    /// Adds the comment, labels, etc. of `other` (a duplicate of self) to self
+    fn merge(&mut self, other: Player) {
+        if !other.comment.is_empty() && other.comment != self.comment {
+            if !self.comment.is_empty() {
+                self.comment.push('\n');
+            }
+            self.comment.push_str(&other.comment);
+            self.lowercase_comment = self.comment.to_lowercase();
+        }
+        for label in other.labels.iter() {
+            self.add_label(label)
+        }
+        for context in other.contexts.iter() {
+            self.add_context(context)
+        }
+        self.last_seen = self.last_seen.max(other.last_seen);
+        self.first_met = match (self.first_met, other.first_met) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.encounter_count += other.encounter_count;
+        self.color = self.color.or(other.color);
+        self.profession = self.profession.take().or(other.profession);
+        self.pinned |= other.pinned;
+        self.ignored |= other.ignored;
+        self.added_manually |= other.added_manually;
+        self.rating = self.rating.max(other.rating);
+        if other.in_squad && !self.in_squad {
+            self.in_squad = true;
+            self.joined_at = other.joined_at;
+            self.subgroup = other.subgroup;
+            self.is_commander = other.is_commander;
+            self.left_at = None;
+        }
+    }
+
+    fn add_context(&mut self, context: &str) {
+        if !context.is_empty() && !self.contexts.iter().any(|c| c == context) {
+            self.contexts.push(context.to_string())
+        }
+    }
+
     fn to_toml(&self) -> Value {
         let mut toml_map = Map::new();
 
         toml_map.insert("name".to_string(), Value::String(self.name.clone()));
         toml_map.insert("comment".to_string(), Value::String(self.comment.clone()));
+        if !self.contexts.is_empty() {
+            let contexts = self.contexts.iter().map(|context| Value::String(context.clone())).collect();
+            toml_map.insert("contexts".to_string(), Value::Array(contexts));
+        }
+        if !self.labels.is_empty() {
+            let labels = self.labels.iter().map(|label| Value::String(label.clone())).collect();
+            toml_map.insert("labels".to_string(), Value::Array(labels));
+        }
+        if let Some(last_seen) = self.last_seen {
+            toml_map.insert("last_seen".to_string(), Value::Integer(last_seen));
+        }
+        if let Some(first_met) = self.first_met {
+            toml_map.insert("first_met".to_string(), Value::Integer(first_met));
+        }
+        toml_map.insert("encounters".to_string(), Value::Integer(self.encounter_count as i64));
+        if let Some(color) = self.color {
+            toml_map.insert("color".to_string(), color_to_toml(color));
+        }
+        if let Some(profession) = &self.profession {
+            toml_map.insert("profession".to_string(), Value::String(profession.clone()));
+        }
+        if self.pinned {
+            toml_map.insert("pinned".to_string(), Value::Boolean(true));
+        }
+        if self.ignored {
+            toml_map.insert("ignored".to_string(), Value::Boolean(true));
+        }
+        if self.added_manually {
+            toml_map.insert("added_manually".to_string(), Value::Boolean(true));
+        }
+        if self.rating != 0 {
+            toml_map.insert("rating".to_string(), Value::Integer(self.rating as i64));
+        }
 
         Value::Table(toml_map)
     }
@@ -39,45 +252,75 @@ impl Player {
 
 struct PlayerVecMap {
     player_list: Vec<Player>,
-    name_dict: HashMap<String, usize>
+    /// Keyed by lowercase name, since account names are case insensitive
+    name_dict: HashMap<String, usize>,
+    /// Players removed by the last delete() or delete_all(), with their former indices, so it can be undone
+    last_deleted: Vec<(usize, Player)>,
+    /// Never delete players automatically, and save them even without a comment
+    keep_all: bool,
+    /// With keep_all, how many players without a comment are saved at most, the most recently
+    /// seen ones. 0 means no limit
+    keep_all_limit: u32,
+    /// How many players can be added by hand, see check_new_player(). 0 means no limit
+    max_players: u32,
 }
 
 impl PlayerVecMap {
     fn new() -> PlayerVecMap {
         PlayerVecMap {
             player_list: Vec::new(),
-            name_dict: HashMap::new()
+            name_dict: HashMap::new(),
+            last_deleted: Vec::new(),
+            keep_all: false,
+            keep_all_limit: DEFAULT_KEEP_ALL_LIMIT,
+            max_players: DEFAULT_MAX_PLAYERS,
         }
     }
 
-    fn is_deletable(&self, username: &str) -> bool {
-        let mut delete = false;
-        if let Some(idx) = self.name_dict.get(username) {
-            if let Some(player) = self.player_list.get(*idx) {
-                // Only delete if there is no comment
-                delete = player.comment == ""
-            }
-        };
+    /// Whether `username` can be added by hand: names must be at most MAX_NAME_LEN characters long,
+    /// and the list can't be grown past max_players. Players joining the squad are always added
+    fn check_new_player(&self, username: &str) -> Result<(), String> {
+        check_name_len(username)?;
+        if self.max_players != 0 && self.player_list.len() >= self.max_players as usize {
+            return Err(format!("The list is full ({} players), see \"Max players\" in the options", self.max_players))
+        }
+
+        Ok(())
+    }
 
-        delete
+    fn is_deletable(&self, username: &str) -> bool {
+        // Only delete if there is no comment
+        !self.keep_all && self.get(username).map_or(false, |player| player.is_blank())
     }
 
+    /// Deletes the player if they have no comment, otherwise marks them as out of the squad.
+    /// Does nothing if the player isn't in the list
     fn user_left(&mut self, username: &str) {
-        let delete = self.is_deletable(username);
-        if delete {
-            let index = self.name_dict.remove(username).unwrap();
-            self.delete_at(index)
+        if self.is_deletable(username) {
+            // Not undoable, there's nothing to lose
+            self.remove(username);
+        } else if let Some(player) = self.get_mut(username) {
+            player.leave();
         }
+    }
 
-        if let Some(index) = self.name_dict.get(username) {
-            let player = &mut self.player_list[*index];
-            player.in_squad = false;
-        }
+    /// Looks up a player by name. Prefer this over indexing self.player_list directly
+    fn get(&self, username: &str) -> Option<&Player> {
+        let index = *self.name_dict.get(&username.to_lowercase())?;
+        self.player_list.get(index)
+    }
+
+    fn get_mut(&mut self, username: &str) -> Option<&mut Player> {
+        let index = *self.name_dict.get(&username.to_lowercase())?;
+        self.player_list.get_mut(index)
     }
 
     /// deletes ONLY from self.player_list. Use delete() to also delete from self.name_dict
-    fn delete_at(&mut self, index: usize) {
-        self.player_list.remove(index);
+    fn delete_at(&mut self, index: usize) -> Option<Player> {
+        if index >= self.player_list.len() {
+            return None
+        }
+        let player = self.player_list.remove(index);
 
         // After deleting the elements in the vec, all elements after it are shifted to the left. Update the indices
         for (_, idx) in self.name_dict.iter_mut() {
@@ -85,584 +328,5075 @@ impl PlayerVecMap {
                 *idx -= 1
             }
         }
+
+        Some(player)
+    }
+
+    /// deletes from BOTH self.player_list and self.name_dict, without being able to undo it
+    fn remove(&mut self, username: &str) -> Option<(usize, Player)> {
+        let index = self.name_dict.remove(&username.to_lowercase())?;
+        let player = self.delete_at(index)?;
+        if self.player_list.len() > self.name_dict.len() {
+            // There are duplicates from old configs, which may have shared the removed key
+            self.rebuild_name_dict();
+        }
+        Some((index, player))
     }
 
-    /// deletes from BOTH self.player_list and self.name_dict. Use delete_at() to only delete from self.player_list
+    /// Like remove(), but can be undone with undo_delete()
     fn delete(&mut self, username: &str) {
-        if let Some(index) = self.name_dict.remove(username) {
-            self.player_list.remove(index);
+        if let Some(deleted) = self.remove(username) {
+            self.last_deleted = vec![deleted];
+        }
+    }
 
-            // After deleting the elements in the vec, all elements after it are shifted to the left. Update the indices
-            for (_, idx) in self.name_dict.iter_mut() {
-                if *idx > index {
-                    *idx -= 1
-                }
+    /// Like delete(), but deletes the player at `index` if it's still `username`. Unlike the name,
+    /// the index tells apart the duplicates from old configs
+    fn delete_row(&mut self, index: usize, username: &str) {
+        match self.player_list.get(index) {
+            Some(player) if player.name == username => self.delete_where(|i, _| i == index),
+            _ => self.delete(username),
+        }
+    }
+
+    /// Puts back the players removed by the last delete() or delete_where(). Players
+    /// that have been added again since then get the deleted player's comment, labels, etc.
+    fn undo_delete(&mut self) {
+        let mut restored = Vec::new();
+        for (index, player) in std::mem::take(&mut self.last_deleted) {
+            match self.get_mut(&player.name) {
+                Some(existing) => existing.merge(player),
+                None => restored.push((index, player)),
             }
-        };
+        }
+        // The indices are ascending, so each player goes back to where it was
+        for (index, player) in restored {
+            let index = index.min(self.player_list.len());
+            self.player_list.insert(index, player);
+        }
+        self.rebuild_name_dict();
+    }
+
+    /// Marks every player as out of the squad
+    fn leave_all(&mut self) {
+        for player in self.player_list.iter_mut() {
+            player.leave();
+        }
     }
 
-    /// Deletes all players whose comment is an empty string
+    /// Deletes all players whose comment is an empty string, unless pinned or keep_all
     fn delete_all(&mut self) {
-        let mut delete_list = Vec::new();
+        self.leave_all();
+        if !self.keep_all {
+            self.delete_where(|_, player| player.is_blank());
+        }
+    }
 
-        // The indices will be in reverse order so we can delete
-        // them in same order without shifting any to-delete elements
-        for player in self.player_list.iter_mut().rev() {
-            player.in_squad = false;
-            if player.comment == "" {
-                if let Some(idx) = self.name_dict.remove(&player.name) {
-                    delete_list.push(idx)
-                }
+    /// Deletes every player for which `condition(index, player)` is true. Can be undone with undo_delete()
+    fn delete_where(&mut self, mut condition: impl FnMut(usize, &Player) -> bool) {
+        // A single pass and reindex instead of delete() for each player, which would be O(n²)
+        let mut deleted = Vec::new();
+        for (index, player) in std::mem::take(&mut self.player_list).into_iter().enumerate() {
+            if condition(index, &player) {
+                deleted.push((index, player))
+            } else {
+                self.player_list.push(player)
             }
         }
+        self.rebuild_name_dict();
+        if !deleted.is_empty() {
+            self.last_deleted = deleted;
+        }
+    }
+
+    /// Points self.name_dict at the current indices of self.player_list
+    fn rebuild_name_dict(&mut self) {
+        self.name_dict = self.player_list.iter()
+            .enumerate()
+            .map(|(idx, player)| (player.lowercase_name.clone(), idx))
+            .collect();
+    }
+
+    /// Every label used by any player, sorted and without duplicates
+    fn all_labels(&self) -> Vec<String> {
+        let mut labels: Vec<_> = self.player_list.iter()
+            .flat_map(|player| player.labels.iter().cloned())
+            .collect();
+        labels.sort();
+        labels.dedup();
+        labels
+    }
+
+    /// `context` is added to the player's seen-in contexts, unless it's empty
+    /// Adds the player if needed, and updates everything about it except the comment
+    fn join(&mut self, username: &str, context: &str) {
+        let player = self.upsert(username);
+        // The squad has the right capitalization, in case it was added by hand differently
+        if player.name != username {
+            player.name = username.to_string();
+        }
+        // Rejoining counts as a new encounter, but updates while in the squad don't
+        if !player.in_squad {
+            player.joined_at = Some(Instant::now());
+            player.encounter_count += 1;
+        }
+        player.in_squad = true;
+        player.last_seen = Some(unix_now());
+        // Players from configs older than first_met are first met now, as far as the list knows
+        player.first_met.get_or_insert(unix_now());
+        player.left_at = None;
+        player.add_context(context);
+    }
 
-        for idx in delete_list {
-            self.delete_at(idx)
+    /// Adds `context` to every player currently in the squad
+    fn add_context_to_squad(&mut self, context: &str) {
+        for player in self.player_list.iter_mut().filter(|player| player.in_squad) {
+            player.add_context(context)
         }
     }
 
-    fn join(&mut self, username: &str) {
-        self.add_player(username, "".to_string());
+    /// Indices of self.player_list in the order they should be displayed
+    fn sorted_indices(&self, sort: Sort) -> Vec<usize> {
+        let mut indices: Vec<_> = (0..self.player_list.len()).collect();
+        indices.sort_by(|a, b| {
+            let (player_a, player_b) = (&self.player_list[*a], &self.player_list[*b]);
+            let ordering = match sort.column {
+                SortColumn::Insertion => a.cmp(b),
+                SortColumn::Name => player_a.lowercase_name.cmp(&player_b.lowercase_name),
+                SortColumn::Comment => player_a.lowercase_comment.cmp(&player_b.lowercase_comment),
+                SortColumn::LastSeen => player_a.last_seen.cmp(&player_b.last_seen),
+                SortColumn::Encounters => player_a.encounter_count.cmp(&player_b.encounter_count),
+                SortColumn::Profession => player_a.profession.cmp(&player_b.profession),
+                SortColumn::Subgroup => player_a.subgroup.cmp(&player_b.subgroup),
+                SortColumn::Rating => player_a.rating.cmp(&player_b.rating),
+                SortColumn::Chat => player_a.last_chat.cmp(&player_b.last_chat),
+            };
 
-        if let Some(index) = self.name_dict.get(username) {
-            let player = &mut self.player_list[*index];
-            player.in_squad = true;
-        };
+            if sort.ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+        // Stable, so pinned and ignored players keep the sort order among themselves
+        indices.sort_by_key(|i| (self.player_list[*i].ignored, !self.player_list[*i].pinned));
+
+        indices
+    }
+
+    /// Adds `player` at the end of the list, which must not have it already
+    fn insert(&mut self, player: Player) {
+        self.name_dict.insert(player.lowercase_name.clone(), self.player_list.len());
+        self.player_list.push(player);
     }
 
-    fn add_player(&mut self, username: &str, comment: String) {
-        let add = !self.name_dict.contains_key(username);
+    /// Returns false if the player was already in the list, in which case nothing is changed
+    fn add_player(&mut self, username: &str, comment: String, added_manually: bool) -> bool {
+        let add = !self.name_dict.contains_key(&username.to_lowercase());
         if add {
-            let new_item_index = self.player_list.len();
-            self.name_dict.insert(username.to_string(), new_item_index);
-            self.player_list.push(Player {
-                name: username.to_string(),
-                lowercase_name: username.to_lowercase(),
-                comment,
-                lowercase_comment: "".to_string(),
-                in_squad: false
+            self.insert(Player::new(username, comment, added_manually));
+        }
+
+        add
+    }
+
+    /// The player called `username`, added without a comment if it isn't in the list yet.
+    /// Unlike add_player(), the existing player can be updated
+    fn upsert(&mut self, username: &str) -> &mut Player {
+        let index = match self.name_dict.get(&username.to_lowercase()) {
+            Some(index) => *index,
+            None => {
+                self.insert(Player::new(username, String::new(), false));
+                self.player_list.len() - 1
+            }
+        };
+        &mut self.player_list[index]
+    }
+
+    fn clear_comments(&mut self) {
+        for player in self.player_list.iter_mut() {
+            player.comment.clear();
+            player.lowercase_comment.clear();
+        }
+    }
+
+    /// Appends `comment` as a new line of the player's comment
+    fn append_comment(&mut self, username: &str, comment: &str) {
+        if let Some(player) = self.get_mut(username) {
+            if !player.comment.is_empty() {
+                player.comment.push('\n');
+            }
+            player.comment.push_str(comment);
+            player.lowercase_comment = player.comment.to_lowercase();
+        }
+    }
+
+    /// Renames the player at `index` if it's still `username`, like delete_row(), or else the one called `username`.
+    /// Fails if `new_name` is empty, too long or already in the list
+    fn rename(&mut self, index: usize, username: &str, new_name: &str) -> Result<(), String> {
+        if new_name.is_empty() {
+            return Err("The name can't be empty".to_string())
+        }
+        check_name_len(new_name)?;
+        let (lowercase_name, new_lowercase_name) = (username.to_lowercase(), new_name.to_lowercase());
+        // Only changing the capitalization doesn't conflict with the player itself
+        if new_lowercase_name != lowercase_name && self.name_dict.contains_key(&new_lowercase_name) {
+            return Err(format!("{new_name} is already in the list"))
+        }
+        let index = match self.player_list.get(index) {
+            Some(player) if player.name == username => index,
+            _ => *self.name_dict.get(&lowercase_name).ok_or(format!("{username} isn't in the list"))?,
+        };
+        let player = &mut self.player_list[index];
+        player.name = new_name.to_string();
+        player.lowercase_name = new_lowercase_name;
+        // Duplicates share their name_dict entry, so it can't just be moved
+        self.rebuild_name_dict();
+
+        Ok(())
+    }
+
+    /// Merges players whose names only differ in capitalization into the first of them.
+    /// Returns how many players were merged away
+    fn merge_duplicates(&mut self) -> usize {
+        let mut player_list: Vec<Player> = Vec::new();
+        let mut name_dict = HashMap::new();
+        let mut merged = 0;
+        for player in std::mem::take(&mut self.player_list) {
+            match name_dict.get(&player.lowercase_name) {
+                Some(&index) => {
+                    player_list[index].merge(player);
+                    merged += 1;
+                },
+                None => {
+                    name_dict.insert(player.lowercase_name.clone(), player_list.len());
+                    player_list.push(player);
+                }
+            }
+        }
+        self.player_list = player_list;
+        self.name_dict = name_dict;
+
+        merged
+    }
+
+    /// Inconsistencies between player_list and name_dict, which would be bugs. Duplicates from
+    /// old configs aren't reported, they're expected until merged
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (name, index) in self.name_dict.iter() {
+            match self.player_list.get(*index) {
+                Some(player) if &player.lowercase_name == name => (),
+                Some(player) => problems.push(format!("{name} points at {index}, which is {}", player.name)),
+                None => problems.push(format!("{name} points at {index}, past the end of the list")),
+            }
+        }
+        for (index, player) in self.player_list.iter().enumerate() {
+            if !self.name_dict.contains_key(&player.lowercase_name) {
+                problems.push(format!("{} ({index}) can't be looked up by name", player.name))
+            }
+        }
+
+        problems
+    }
+
+    /// For each player, whether it's written to the config
+    fn saved(&self) -> Vec<bool> {
+        let mut saved: Vec<_> = self.player_list.iter().map(|player| !player.is_blank()).collect();
+        if self.keep_all {
+            let mut blank: Vec<_> = (0..self.player_list.len()).filter(|i| !saved[*i]).collect();
+            if self.keep_all_limit != 0 {
+                // Most recently seen first. Players never seen come last
+                blank.sort_by(|a, b| self.player_list[*b].last_seen.cmp(&self.player_list[*a].last_seen));
+                blank.truncate(self.keep_all_limit as usize);
+            }
+            for i in blank {
+                saved[i] = true
+            }
+        }
+
+        saved
+    }
+
+    /// Players whose name is the same as another player's except for capitalization
+    fn duplicate_count(&self) -> usize {
+        self.player_list.len() - self.name_dict.len()
+    }
+
+    /// Non-empty comments that 2 or more players have, with the names of those players.
+    /// In the order the comments first appear in
+    fn shared_comments(&self) -> Vec<(String, Vec<String>)> {
+        let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+        let mut group_index = HashMap::new();
+        for player in self.player_list.iter().filter(|player| !player.comment.is_empty()) {
+            let index = *group_index.entry(player.comment.as_str()).or_insert_with(|| {
+                groups.push((player.comment.clone(), Vec::new()));
+                groups.len() - 1
             });
+            groups[index].1.push(player.name.clone());
+        }
+        groups.retain(|(_, names)| names.len() >= 2);
+
+        groups
+    }
+
+    fn set_comment(&mut self, username: &str, comment: &str) {
+        if let Some(player) = self.get_mut(username) {
+            player.comment = comment.to_string();
+            player.lowercase_comment = player.comment.to_lowercase();
         }
     }
 }
 
-struct Filters {
-    user_filter_str: String,
-    comment_filter_str: String
+#[derive(Clone, Copy, PartialEq)]
+enum SortColumn {
+    Insertion,
+    Name,
+    Comment,
+    LastSeen,
+    Encounters,
+    Profession,
+    Subgroup,
+    Rating,
+    Chat,
 }
 
-impl Filters {
-    fn new() -> Filters {
-        Filters {
-            user_filter_str: String::new(),
-            comment_filter_str: String::new()
+impl SortColumn {
+    const ALL: [SortColumn; 9] = [SortColumn::Insertion, SortColumn::Name, SortColumn::Comment, SortColumn::LastSeen, SortColumn::Encounters, SortColumn::Profession, SortColumn::Subgroup, SortColumn::Rating, SortColumn::Chat];
+
+    fn name(&self) -> &'static str {
+        match self {
+            SortColumn::Insertion => "Join order",
+            SortColumn::Name => "Name",
+            SortColumn::Comment => "Comment",
+            SortColumn::LastSeen => "Last seen",
+            SortColumn::Encounters => "Encounters",
+            SortColumn::Profession => "Profession",
+            SortColumn::Subgroup => "Subgroup",
+            SortColumn::Rating => "Rating",
+            SortColumn::Chat => "Last chat message",
+        }
+    }
+
+    fn config_name(&self) -> &'static str {
+        match self {
+            SortColumn::Insertion => "Insertion",
+            SortColumn::Name => "Name",
+            SortColumn::Comment => "Comment",
+            SortColumn::LastSeen => "LastSeen",
+            SortColumn::Encounters => "Encounters",
+            SortColumn::Profession => "Profession",
+            SortColumn::Subgroup => "Subgroup",
+            SortColumn::Rating => "Rating",
+            SortColumn::Chat => "Chat",
         }
     }
+
+    fn from_config_name(name: &str) -> Option<SortColumn> {
+        SortColumn::ALL.into_iter().find(|column| column.config_name() == name)
+    }
 }
 
-struct Flags {
-    extras_initialized: bool,
-    display_window: bool,
-    show_all: bool,
+/// Columns of the player table
+#[derive(Clone, Copy, PartialEq)]
+enum TableColumn {
+    Name,
+    Comment,
+    LastSeen,
+    Encounters,
+    Profession,
+    Subgroup,
+    Rating,
+    Chat,
 }
 
-impl Flags {
-    fn new() -> Flags {
-        Flags {
-            extras_initialized: false,
-            display_window: false,
-            show_all: false
+impl TableColumn {
+    const ALL: [TableColumn; 8] = [TableColumn::Name, TableColumn::Profession, TableColumn::Subgroup, TableColumn::Rating, TableColumn::Chat, TableColumn::Comment, TableColumn::LastSeen, TableColumn::Encounters];
+
+    fn header(&self) -> &'static str {
+        match self {
+            // max character length of account name = 32 characters
+            TableColumn::Name => "name",
+            TableColumn::Comment => "comment",
+            TableColumn::LastSeen => "last seen",
+            TableColumn::Encounters => "encounters",
+            TableColumn::Profession => "profession",
+            TableColumn::Subgroup => "group",
+            TableColumn::Rating => "rating",
+            TableColumn::Chat => "chat",
+        }
+    }
+
+    fn sort_column(&self) -> SortColumn {
+        match self {
+            TableColumn::Name => SortColumn::Name,
+            TableColumn::Comment => SortColumn::Comment,
+            TableColumn::LastSeen => SortColumn::LastSeen,
+            TableColumn::Encounters => SortColumn::Encounters,
+            TableColumn::Profession => SortColumn::Profession,
+            TableColumn::Subgroup => SortColumn::Subgroup,
+            TableColumn::Rating => SortColumn::Rating,
+            TableColumn::Chat => SortColumn::Chat,
+        }
+    }
+
+    fn is_visible(&self, flags: &Flags) -> bool {
+        match self {
+            TableColumn::Encounters => flags.show_encounters,
+            TableColumn::Profession => flags.show_profession,
+            TableColumn::Subgroup => flags.show_subgroup,
+            TableColumn::Rating => flags.show_rating,
+            TableColumn::Chat => flags.show_chat,
+            _ => true,
         }
     }
 }
 
-struct State {
-    players: PlayerVecMap,
-    self_name: String,
-    flags: Flags,
-    filters: Filters,
-    inactive_color: [f32;4],
-    comment_size: [f32;2],
-    add_user_text: String,
-    shortcut_char: Option<VirtualKey>,
-    listening_to_key: bool,
+#[derive(Clone, Copy, PartialEq)]
+struct Sort {
+    column: SortColumn,
+    ascending: bool,
 }
 
-impl State {
-    fn new() -> State {
-        State {
-            players: PlayerVecMap::new(),
-            self_name: "".to_string(),
-            flags: Flags::new(),
-            filters: Filters::new(),
-            inactive_color: DEFAULT_INACTIVE_COLOR,
-            comment_size: DEFAULT_COMMENT_SIZE,
-            add_user_text: "".to_string(),
-            shortcut_char: None,
-            listening_to_key: false
+impl Sort {
+    fn new() -> Sort {
+        Sort {
+            column: SortColumn::Insertion,
+            ascending: true
+        }
+    }
+
+    /// None if `column` isn't a valid SortColumn
+    fn from_toml(column: Option<Value>, ascending: Option<Value>) -> Option<Sort> {
+        let column = match column {
+            Some(Value::String(s)) => SortColumn::from_config_name(&s)?,
+            _ => return None
+        };
+
+        Some(Sort {
+            column,
+            ascending: read_bool(ascending, true)
+        })
+    }
+
+    /// Flags of the table column that sorts by `column`, so the table starts sorted like self
+    fn table_column_flags(&self, column: SortColumn) -> TableColumnFlags {
+        if self.column != column {
+            TableColumnFlags::empty()
+        } else if self.ascending {
+            TableColumnFlags::DEFAULT_SORT
+        } else {
+            TableColumnFlags::DEFAULT_SORT | TableColumnFlags::PREFER_SORT_DESCENDING
         }
     }
 }
 
-static mut STATE: Lazy<Mutex<State>> = Lazy::new(|| Mutex::new(State::new()));
-const CONFIG_PATH: &'static str = "addons/arcdps/player_list.toml";
-const TMP_PATH: &'static str = "addons/arcdps/player_list.tmp";
+#[derive(Clone, Copy, PartialEq)]
+enum MatchMode {
+    StartsWith,
+    Contains,
+    /// The characters of the filter appear in order, see fuzzy_score()
+    Fuzzy,
+}
 
-const PLAYERS: &'static str = "Players";
-const OPENED_WINDOW: &'static str = "WindowOpen";
-const INACTIVE_COLOR: &'static str = "InactiveColor";
-const SHOW_ALL: &'static str = "ShowAll";
-const COMMENT_SIZE: &'static str = "CommentSize";
-const DEFAULT_INACTIVE_COLOR: [f32;4] = [0.5,0.5,0.5,1.0];
-const DEFAULT_COMMENT_SIZE: [f32;2] = [300.0, 20.0];
-const SHORTCUT: &'static str = "ShortcutKey";
+impl MatchMode {
+    fn config_name(&self) -> &'static str {
+        match self {
+            MatchMode::StartsWith => "StartsWith",
+            MatchMode::Contains => "Contains",
+            MatchMode::Fuzzy => "Fuzzy",
+        }
+    }
 
-fn init() -> Result<(), String> {
-    // May return an error to indicate load failure
+    fn from_config_name(name: &str) -> Option<MatchMode> {
+        [MatchMode::StartsWith, MatchMode::Contains, MatchMode::Fuzzy].into_iter().find(|mode| mode.config_name() == name)
+    }
+}
+
+/// A player is shown if it matches every filter
+#[derive(Clone)]
+struct Filters {
+    user_filter_str: String,
+    comment_filter_str: String,
+    /// Matches if either the user name or the comment matches
+    quick_filter_str: String,
+    /// Only show players with these labels
+    label_filters: Vec<String>,
+    /// Players must have all of label_filters instead of any of them
+    label_match_all: bool,
+    /// Only show players with at least this rating. 0 shows unrated players too
+    min_rating: u8,
+    /// How the text filters are matched. This is a setting, so it's not part of filter presets
+    match_mode: MatchMode,
+    /// Also a setting
+    case_sensitive: bool,
+}
+
+impl Filters {
+    fn new() -> Filters {
+        Filters {
+            user_filter_str: String::new(),
+            comment_filter_str: String::new(),
+            quick_filter_str: String::new(),
+            label_filters: Vec::new(),
+            label_match_all: false,
+            min_rating: 0,
+            match_mode: MatchMode::Contains,
+            case_sensitive: false,
+        }
+    }
+
+    /// Whether `player` should be in the table. The filters must have been normalized with normalize()
+    fn shows(&self, player: &Player, user_filter: &str, comment_filter: &str, quick_filter: &str, flags: &Flags) -> bool {
+        (flags.show_all || player.in_squad || player.pinned)
+            && (!flags.commented_only || !player.comment.is_empty())
+            && (flags.show_ignored || !player.ignored)
+            && self.matches_field(&player.name, &player.lowercase_name, user_filter)
+            && self.matches_field(&player.comment, &player.lowercase_comment, comment_filter)
+            && (self.matches_field(&player.name, &player.lowercase_name, quick_filter)
+                || self.matches_field(&player.comment, &player.lowercase_comment, quick_filter))
+            && self.matches_labels(player)
+            && player.rating >= self.min_rating
+    }
+
+    /// Prepares `filter` to be passed to matches_field()
+    fn normalize(&self, filter: &str) -> String {
+        if self.case_sensitive {
+            filter.to_string()
+        } else {
+            filter.to_lowercase()
+        }
+    }
+
+    /// `filter` must have been normalized with normalize()
+    fn matches_field(&self, text: &str, lowercase_text: &str, filter: &str) -> bool {
+        if self.case_sensitive {
+            self.matches_text(text, filter)
+        } else {
+            self.matches_text(lowercase_text, filter)
+        }
+    }
 
-    let toml_string = std::fs::read_to_string(CONFIG_PATH).unwrap_or_default();
-    let mut config = match toml::from_str::<Value>(&toml_string)
-        .unwrap_or(Value::Table(Map::new())) {
-            Value::Table(config) => config,
-            _ => Map::new()
+    /// Byte range of `text` matched by `filter`, if any. `filter` must have been normalized with normalize()
+    fn match_range(&self, text: &str, lowercase_text: &str, filter: &str) -> Option<(usize, usize)> {
+        if filter.is_empty() {
+            return None
+        }
+        let searched = if self.case_sensitive { text } else { lowercase_text };
+        let start = match self.match_mode {
+            MatchMode::StartsWith => searched.starts_with(filter).then_some(0)?,
+            MatchMode::Contains => searched.find(filter)?,
+            // Not a single range
+            MatchMode::Fuzzy => return None,
         };
+        if self.case_sensitive {
+            return Some((start, start + filter.len()))
+        }
 
-    let player_list = init_player_list(&mut config);
-    let display_window = match config.remove(OPENED_WINDOW) {
-        Some(Value::Boolean(b)) => b,
-        _ => false,
-    };
-    let inactive_color = match config.remove(INACTIVE_COLOR) {
-        Some(Value::Array(mut arr)) => {
-            if arr.len() == 4 {
-                let a = arr.remove(3);
-                let b = arr.remove(2);
-                let g = arr.remove(1);
-                let r = arr.remove(0);
-                if let (Value::Float(r), Value::Float(g), Value::Float(b), Value::Float(a)) = (r,g,b,a) {
-                    [r as f32,g as f32,b as f32,a as f32]
-                } else {
-                    DEFAULT_INACTIVE_COLOR
-                }
-            } else {
-                DEFAULT_INACTIVE_COLOR
+        // Lowercasing can change the length of some characters, so the range has to be mapped back to `text`
+        let end = start + filter.len();
+        let (mut text_start, mut lowercase_index) = (None, 0);
+        for (index, c) in text.char_indices() {
+            if lowercase_index == start {
+                text_start = Some(index)
             }
-        },
-        _ => DEFAULT_INACTIVE_COLOR,
-    };
-    let comment_size = match config.remove(COMMENT_SIZE) {
-        Some(Value::Array(mut arr)) => {
-            if arr.len() == 2 {
-                let h = arr.remove(1);
-                let w = arr.remove(0);
-                if let (Value::Float(w), Value::Float(h)) = (w, h) {
-                    [w as f32,h as f32]
-                } else {
-                    DEFAULT_COMMENT_SIZE
+            if lowercase_index == end {
+                return Some((text_start?, index))
+            }
+            lowercase_index += c.to_lowercase().map(char::len_utf8).sum::<usize>();
+        }
+
+        (lowercase_index == end).then_some((text_start?, text.len()))
+    }
+
+    /// Stops filtering, keeping the settings of how to filter
+    fn clear(&mut self) {
+        self.apply_preset(&Filters::new())
+    }
+
+    /// Replaces what is being filtered with `preset`, keeping the settings of how to filter
+    fn apply_preset(&mut self, preset: &Filters) {
+        self.user_filter_str = preset.user_filter_str.clone();
+        self.comment_filter_str = preset.comment_filter_str.clone();
+        self.quick_filter_str = preset.quick_filter_str.clone();
+        self.label_filters = preset.label_filters.clone();
+        self.label_match_all = preset.label_match_all;
+        self.min_rating = preset.min_rating;
+    }
+
+    /// `filter` matches `text` (an empty filter matches anything)
+    fn matches_text(&self, text: &str, filter: &str) -> bool {
+        match self.match_mode {
+            MatchMode::StartsWith => text.starts_with(filter),
+            MatchMode::Contains => text.contains(filter),
+            // Anything Contains matches too, even short filters that can't reach the minimum score
+            MatchMode::Fuzzy => text.contains(filter) || fuzzy_score(text, filter).map_or(false, |score| score >= fuzzy_min_score(filter)),
+        }
+    }
+
+    /// How well `player`'s name matches the user filter and the quick filter, to sort by it in fuzzy mode.
+    /// The filters must have been normalized with normalize()
+    fn fuzzy_rank(&self, player: &Player, user_filter: &str, quick_filter: &str) -> u32 {
+        let name = if self.case_sensitive { &player.name } else { &player.lowercase_name };
+        [user_filter, quick_filter].into_iter()
+            .filter(|filter| !filter.is_empty())
+            .filter_map(|filter| fuzzy_score(name, filter))
+            .sum()
+    }
+
+    fn matches_labels(&self, player: &Player) -> bool {
+        if self.label_filters.is_empty() {
+            return true
+        }
+
+        if self.label_match_all {
+            self.label_filters.iter().all(|label| player.labels.contains(label))
+        } else {
+            self.label_filters.iter().any(|label| player.labels.contains(label))
+        }
+    }
+
+    fn add_label_filter(&mut self, label: String) {
+        if !self.label_filters.contains(&label) {
+            self.label_filters.push(label)
+        }
+    }
+
+    fn to_toml(&self) -> Map<String, Value> {
+        let mut toml_map = Map::new();
+
+        toml_map.insert("user_filter".to_string(), Value::String(self.user_filter_str.clone()));
+        toml_map.insert("comment_filter".to_string(), Value::String(self.comment_filter_str.clone()));
+        toml_map.insert("quick_filter".to_string(), Value::String(self.quick_filter_str.clone()));
+        if !self.label_filters.is_empty() {
+            let labels = self.label_filters.iter().map(|label| Value::String(label.clone())).collect();
+            toml_map.insert("label_filters".to_string(), Value::Array(labels));
+        }
+        toml_map.insert("label_match_all".to_string(), Value::Boolean(self.label_match_all));
+        toml_map.insert("min_rating".to_string(), Value::Integer(self.min_rating as i64));
+
+        toml_map
+    }
+
+    fn from_toml(properties: &mut Map<String, Value>) -> Filters {
+        let mut filters = Filters::new();
+        if let Some(Value::String(user_filter_str)) = properties.remove("user_filter") {
+            filters.user_filter_str = user_filter_str
+        }
+        if let Some(Value::String(comment_filter_str)) = properties.remove("comment_filter") {
+            filters.comment_filter_str = comment_filter_str
+        }
+        if let Some(Value::String(quick_filter_str)) = properties.remove("quick_filter") {
+            filters.quick_filter_str = quick_filter_str
+        }
+        filters.label_filters = string_array(properties.remove("label_filters"));
+        if let Some(Value::Boolean(label_match_all)) = properties.remove("label_match_all") {
+            filters.label_match_all = label_match_all
+        }
+        if let Some(Value::Integer(min_rating)) = properties.remove("min_rating") {
+            filters.min_rating = min_rating.clamp(0, MAX_RATING as i64) as u8
+        }
+
+        filters
+    }
+}
+
+struct FilterPreset {
+    name: String,
+    filters: Filters,
+}
+
+impl FilterPreset {
+    fn to_toml(&self) -> Value {
+        let mut toml_map = self.filters.to_toml();
+        toml_map.insert("name".to_string(), Value::String(self.name.clone()));
+
+        Value::Table(toml_map)
+    }
+}
+
+/// A squad you were in, and who was in it
+struct SquadSession {
+    /// In unix seconds
+    started_at: i64,
+    /// None if it's the current squad, or the game was closed without leaving it
+    ended_at: Option<i64>,
+    context: String,
+    /// In join order
+    players: Vec<String>,
+}
+
+impl SquadSession {
+    fn to_toml(&self) -> Value {
+        let mut toml_map = Map::new();
+        toml_map.insert("started_at".to_string(), Value::Integer(self.started_at));
+        if let Some(ended_at) = self.ended_at {
+            toml_map.insert("ended_at".to_string(), Value::Integer(ended_at));
+        }
+        if !self.context.is_empty() {
+            toml_map.insert("context".to_string(), Value::String(self.context.clone()));
+        }
+        let players = self.players.iter().map(|name| Value::String(name.clone())).collect();
+        toml_map.insert("players".to_string(), Value::Array(players));
+
+        Value::Table(toml_map)
+    }
+
+    fn from_toml(val: Value) -> Option<SquadSession> {
+        let mut properties = match val {
+            Value::Table(properties) => properties,
+            _ => return None
+        };
+        let started_at = match properties.remove("started_at") {
+            Some(Value::Integer(started_at)) => started_at,
+            _ => return None
+        };
+        let ended_at = match properties.remove("ended_at") {
+            Some(Value::Integer(ended_at)) => Some(ended_at),
+            _ => None
+        };
+        let context = match properties.remove("context") {
+            Some(Value::String(context)) => context,
+            _ => String::new()
+        };
+
+        Some(SquadSession {
+            started_at,
+            ended_at,
+            context,
+            players: string_array(properties.remove("players")),
+        })
+    }
+}
+
+struct Flags {
+    extras_initialized: bool,
+    /// The config couldn't be read nor moved aside, so saving would lose it
+    save_disabled: bool,
+    display_window: bool,
+    show_all: bool,
+    /// Only show players with a comment, in the squad or not depending on show_all
+    commented_only: bool,
+    /// Otherwise ignored players are hidden
+    show_ignored: bool,
+    /// Add squad members that aren't in the list yet. Otherwise only the players already in it are tracked
+    auto_add: bool,
+    /// Append the default comment when manually adding a player that is already in the list
+    append_comment_on_add: bool,
+    /// Fade names from the active to the inactive color when players leave
+    fade_on_leave: bool,
+    /// Save the config as pretty-formatted TOML
+    pretty_config: bool,
+    /// Save players sorted by name instead of in join order
+    sort_saved_players: bool,
+    /// Ask for confirmation before performing a BulkAction
+    confirm_bulk_actions: bool,
+    /// Ask for confirmation before removing the uncommented players when leaving the squad
+    confirm_wipe: bool,
+    /// Start with the sort that was used last instead of the default sort
+    remember_sort: bool,
+    /// Show the players sorted by name instead of in join order when the table isn't sorted
+    sort_alphabetical: bool,
+    /// Show the players in the squad above the rest, each group keeping the sort order
+    squad_first: bool,
+    /// Highlight the part of the names that matches the name filter
+    highlight_matches: bool,
+    /// Show account names without their ".1234" suffix. Only affects the table
+    hide_discriminator: bool,
+    /// Only the names, with the comments in their tooltips and everything else in a collapsed header
+    compact: bool,
+    /// Show the encounters column
+    show_encounters: bool,
+    show_rating: bool,
+    /// Show the profession column
+    show_profession: bool,
+    /// Show the subgroup column
+    show_subgroup: bool,
+    /// Show who wrote in the squad chat recently. Needs unofficial extras
+    show_chat: bool,
+    /// Imported comments replace the existing ones instead of being appended
+    import_overwrite: bool,
+    /// Mirror the list to ROSTER_PATH and answer queries written to QUERY_PATH
+    file_query: bool,
+    /// Select the whole comment when starting to edit it, so typing replaces it
+    select_all_on_edit: bool,
+    /// Size each comment box to its number of lines, with comment_size as the maximum
+    auto_size_comments: bool,
+    /// In fuzzy mode, show the best matches first when the table isn't sorted by a column
+    sort_by_score: bool,
+    /// New players start with the date they were met as their comment, which also keeps them
+    comment_met_date: bool,
+}
+
+impl Flags {
+    fn new() -> Flags {
+        Flags {
+            extras_initialized: false,
+            save_disabled: false,
+            display_window: false,
+            show_all: false,
+            commented_only: false,
+            show_ignored: true,
+            auto_add: true,
+            append_comment_on_add: false,
+            fade_on_leave: false,
+            pretty_config: false,
+            sort_saved_players: false,
+            confirm_bulk_actions: true,
+            confirm_wipe: false,
+            remember_sort: true,
+            sort_alphabetical: false,
+            squad_first: false,
+            highlight_matches: true,
+            hide_discriminator: false,
+            compact: false,
+            show_encounters: false,
+            show_rating: true,
+            show_profession: true,
+            show_subgroup: true,
+            show_chat: false,
+            import_overwrite: false,
+            file_query: false,
+            select_all_on_edit: false,
+            auto_size_comments: false,
+            sort_by_score: true,
+            comment_met_date: false,
+        }
+    }
+}
+
+struct State {
+    players: PlayerVecMap,
+    self_in_squad: bool,
+    /// Lowercase names of the players that were new to the list when they joined the current squad.
+    /// Kept even if they are deleted, to count them
+    new_in_squad: HashSet<String>,
+    self_name: String,
+    flags: Flags,
+    filters: Filters,
+    filter_presets: Vec<FilterPreset>,
+    /// Index of the last applied preset in self.filter_presets
+    current_preset: Option<usize>,
+    preset_name_text: String,
+    /// Text of the "add label" input in the player context menu
+    label_text: String,
+    /// Text of the "rename" input in the player context menu
+    rename_text: String,
+    /// Text of the "dated note" input in the player context menu
+    note_text: String,
+    /// Why the last rename failed
+    rename_status: Option<String>,
+    /// "Delete filtered" also deletes players with a comment
+    delete_commented: bool,
+    /// Bulk action waiting for the user's confirmation
+    pending_bulk_action: Option<BulkAction>,
+    /// When the pending wipe was requested, to perform it anyway after WIPE_CONFIRM_TIMEOUT
+    wipe_requested_at: Option<Instant>,
+    /// Whether the window was open for each account (profile), as of the last load
+    profile_display_window: HashMap<String, bool>,
+    last_file_query: Option<Instant>,
+    /// What was last written to ROSTER_PATH, so it's only rewritten when the list changes
+    last_roster: String,
+    /// Feedback about the last action taken in the options tab
+    options_status: Option<String>,
+    /// Show the internal state in the options, for debugging
+    show_diagnostics: bool,
+    /// Result of the last "Find duplicate comments". None if it hasn't been used, or was hidden
+    shared_comments: Option<Vec<(String, Vec<String>)>>,
+    inactive_color: [f32;4],
+    comment_size: [f32;2],
+    /// Maximum characters of a comment. 0 means no limit
+    comment_max_len: u32,
+    /// Of the row background of pinned and colored players. 0 disables it
+    row_tint_alpha: f32,
+    /// Fraction of the width of the name and comment columns that the name column takes
+    column_split: f32,
+    /// Something may have changed since the last save
+    dirty: bool,
+    /// Seconds between saves while the game is running. 0 only saves when closing the game
+    autosave_interval: u32,
+    last_autosave: Instant,
+    /// Returns whether the save succeeded
+    autosave_thread: Option<JoinHandle<bool>>,
+    /// Texts that can be appended to a comment with a click
+    snippets: Vec<String>,
+    /// Text of the "add snippet" input in the options
+    snippet_text: String,
+    /// Player whose comment was edited last, which snippets are appended to
+    focused_comment: Option<String>,
+    /// Player whose comment is being edited right now, as of the last frame the window was drawn
+    editing_comment: Option<String>,
+    /// Opened by "Open profile", with {name} replaced by the account name
+    profile_url: String,
+    /// Comment of new players when comment_met_date is set, see format_date()
+    met_date_format: String,
+    /// None until the window is first drawn, unless restored from the config
+    window_pos: Option<[f32;2]>,
+    window_size: Option<[f32;2]>,
+    add_user_text: String,
+    /// Feedback about the last "Add" click
+    add_user_status: Option<String>,
+    /// Allow adding names that don't look like account names
+    add_any_name: bool,
+    /// Names pasted in the options, to add them all at once
+    paste_names: String,
+    /// Player whose row will be scrolled to in the next frame
+    scroll_to: Option<String>,
+    /// Row selected with the arrow keys, by name so it follows the player when sorting
+    selected_player: Option<String>,
+    /// Index and name of the player to delete once confirmed, after pressing Delete on the selected row
+    pending_delete: Option<(usize, String)>,
+    shortcut_keys: HashMap<Shortcut, VirtualKey>,
+    listening_to_key: Option<Shortcut>,
+    /// Modifiers that must be held for the shortcuts to trigger
+    shortcut_modifiers: Modifiers,
+    /// Modifiers currently held down
+    held_modifiers: Modifiers,
+    /// An imgui text input has keyboard focus, as of the last frame
+    typing: bool,
+    /// Give keyboard focus to the "add user" input in the next frame
+    focus_add_user: bool,
+    /// Professions reported by arcdps, by lowercase account name. Kept for
+    /// players that arcdps reports before they're in the list
+    professions: HashMap<String, String>,
+    /// Account names of the squad members arcdps is tracking, by agent id. Agent removed
+    /// events only have the id
+    tracked_agents: HashMap<usize, String>,
+    sort: Sort,
+    /// Sort used on startup
+    default_sort: Sort,
+    /// Most recent first
+    recently_left: VecDeque<String>,
+    /// The last MAX_SESSIONS squads, most recent last
+    sessions: VecDeque<SquadSession>,
+    /// Text of the search input of the past squads
+    session_filter: String,
+    /// Context players that join the squad are tagged with
+    context: String,
+    context_text: String,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            players: PlayerVecMap::new(),
+            self_in_squad: false,
+            new_in_squad: HashSet::new(),
+            self_name: "".to_string(),
+            flags: Flags::new(),
+            filters: Filters::new(),
+            filter_presets: Vec::new(),
+            current_preset: None,
+            preset_name_text: String::new(),
+            label_text: String::new(),
+            rename_text: String::new(),
+            note_text: String::new(),
+            rename_status: None,
+            delete_commented: false,
+            pending_bulk_action: None,
+            wipe_requested_at: None,
+            profile_display_window: HashMap::new(),
+            last_file_query: None,
+            last_roster: String::new(),
+            options_status: None,
+            show_diagnostics: false,
+            shared_comments: None,
+            inactive_color: DEFAULT_INACTIVE_COLOR,
+            comment_size: DEFAULT_COMMENT_SIZE,
+            comment_max_len: 0,
+            row_tint_alpha: DEFAULT_ROW_TINT_ALPHA,
+            column_split: DEFAULT_COLUMN_SPLIT,
+            dirty: false,
+            autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+            last_autosave: Instant::now(),
+            autosave_thread: None,
+            snippets: Vec::new(),
+            snippet_text: String::new(),
+            focused_comment: None,
+            editing_comment: None,
+            profile_url: DEFAULT_PROFILE_URL.to_string(),
+            met_date_format: DEFAULT_MET_DATE_FORMAT.to_string(),
+            window_pos: None,
+            window_size: None,
+            add_user_text: "".to_string(),
+            add_user_status: None,
+            add_any_name: false,
+            paste_names: String::new(),
+            scroll_to: None,
+            selected_player: None,
+            pending_delete: None,
+            shortcut_keys: HashMap::new(),
+            listening_to_key: None,
+            shortcut_modifiers: Modifiers::DEFAULT,
+            held_modifiers: Modifiers::NONE,
+            typing: false,
+            focus_add_user: false,
+            professions: HashMap::new(),
+            tracked_agents: HashMap::new(),
+            sort: Sort::new(),
+            default_sort: Sort::new(),
+            recently_left: VecDeque::new(),
+            sessions: VecDeque::new(),
+            session_filter: String::new(),
+            context: String::new(),
+            context_text: String::new(),
+        }
+    }
+
+    fn run_shortcut(&mut self, shortcut: Shortcut) {
+        self.dirty = true;
+        match shortcut {
+            Shortcut::ToggleWindow => self.flags.display_window = !self.flags.display_window,
+            Shortcut::AddLastLeaver => self.add_last_leaver(),
+            Shortcut::CyclePresets => self.cycle_filter_presets(),
+            Shortcut::ToggleShowAll => self.flags.show_all = !self.flags.show_all,
+            Shortcut::ClearFilters => self.filters.clear(),
+            Shortcut::FocusAddUser => {
+                self.flags.display_window = true;
+                self.focus_add_user = true;
+            },
+        }
+    }
+
+    fn apply_filter_preset(&mut self, index: usize) {
+        if let Some(preset) = self.filter_presets.get(index) {
+            self.filters.apply_preset(&preset.filters);
+            self.current_preset = Some(index);
+        }
+    }
+
+    /// Applies the filter preset after the current one, wrapping around at the end
+    fn cycle_filter_presets(&mut self) {
+        if self.filter_presets.is_empty() {
+            return
+        }
+
+        let next = match self.current_preset {
+            Some(index) => (index + 1) % self.filter_presets.len(),
+            None => 0,
+        };
+        self.apply_filter_preset(next)
+    }
+
+    /// Restores whether the window was open for the current account.
+    /// Accounts seen for the first time keep the last state of the window, regardless of account
+    fn apply_profile_display_window(&mut self) {
+        if let Some(open) = self.profile_display_window.get(&self.self_name) {
+            self.flags.display_window = *open
+        }
+    }
+
+    /// Performs `action`, asking for confirmation first if enabled
+    fn request_bulk_action(&mut self, action: BulkAction) {
+        if self.flags.confirm_bulk_actions {
+            self.ask_confirmation(action)
+        } else {
+            self.perform_bulk_action(action)
+        }
+    }
+
+    /// Shows the confirmation for `action`. Only one is shown at a time, so `action` is dropped if another one is open
+    fn ask_confirmation(&mut self, action: BulkAction) {
+        if self.pending_bulk_action.is_some() {
+            self.options_status = Some("Answer the open confirmation first".to_string());
+        } else {
+            self.pending_bulk_action = Some(action)
+        }
+    }
+
+    fn perform_bulk_action(&mut self, action: BulkAction) {
+        self.dirty = true;
+        match action {
+            BulkAction::ReloadConfig => {
+                self.options_status = match reload_config(self) {
+                    Ok(()) => Some("Config reloaded".to_string()),
+                    Err(e) => {
+                        log(LogLevel::Error, &e);
+                        Some(e)
+                    }
+                };
+            },
+            BulkAction::ClearAllComments => self.players.clear_comments(),
+            BulkAction::RemoveUncommented => self.players.delete_all(),
+            BulkAction::DeleteFiltered { include_commented } => {
+                // Decided before deleting anything, since deleting shifts the indices
+                let targets = self.filtered_deletions(include_commented);
+                self.players.delete_where(|index, _| targets[index]);
+            },
+            BulkAction::MergeDuplicates => {
+                let merged = self.players.merge_duplicates();
+                self.options_status = Some(format!("Merged {merged} duplicates"));
+            },
+            BulkAction::AddPastedNames => {
+                let comment = self.new_player_comment();
+                let (added, present, skipped, refused) = add_names(&mut self.players, &self.paste_names, &comment, self.add_any_name, &self.self_name);
+                let mut status = format!("Added {added} players, {present} were already in the list");
+                match (skipped, refused) {
+                    (0, _) => self.paste_names.clear(),
+                    (skipped, Some(refused)) => status.push_str(&format!(", skipped {skipped}. {refused}")),
+                    (skipped, None) => status.push_str(&format!(", skipped {skipped} that aren't account names")),
                 }
-            } else {
-                DEFAULT_COMMENT_SIZE
+                self.options_status = Some(status);
+            },
+        }
+    }
+
+    /// Called when leaving the squad. Removes the players without a comment,
+    /// or waits for confirmation if enabled
+    fn request_wipe(&mut self) {
+        if self.flags.confirm_wipe && !self.players.keep_all {
+            // Players are marked as gone right away, only the removal waits
+            self.players.leave_all();
+            // If another confirmation is open, this one is shown after it's answered
+            if self.pending_bulk_action.is_none() {
+                self.pending_bulk_action = Some(BulkAction::RemoveUncommented);
             }
-        },
-        _ => DEFAULT_COMMENT_SIZE,
+            self.wipe_requested_at = Some(Instant::now());
+        } else {
+            self.players.delete_all()
+        }
+    }
+
+    /// Performs the wipe if it's still unconfirmed after WIPE_CONFIRM_TIMEOUT, or right away if `force`
+    fn resolve_pending_wipe(&mut self, force: bool) {
+        if let Some(requested_at) = self.wipe_requested_at {
+            if force || requested_at.elapsed() >= WIPE_CONFIRM_TIMEOUT {
+                self.wipe_requested_at = None;
+                if let Some(BulkAction::RemoveUncommented) = self.pending_bulk_action {
+                    self.pending_bulk_action = None;
+                }
+                self.perform_bulk_action(BulkAction::RemoveUncommented);
+            } else if self.pending_bulk_action.is_none() {
+                // The confirmation that was open when leaving has been answered
+                self.pending_bulk_action = Some(BulkAction::RemoveUncommented);
+            }
+        }
+    }
+
+    /// Indices of the players shown in the table, in the order they are shown
+    fn visible_indices(&self) -> Vec<usize> {
+        let filters = &self.filters;
+        let players = &self.players;
+        let user_filter = filters.normalize(&filters.user_filter_str);
+        let comment_filter = filters.normalize(&filters.comment_filter_str);
+        let quick_filter = filters.normalize(&filters.quick_filter_str);
+        // Only the displayed order changes, player_list stays in join order
+        let sort = match self.sort.column {
+            SortColumn::Insertion if self.flags.sort_alphabetical => Sort { column: SortColumn::Name, ascending: true },
+            _ => self.sort,
+        };
+        let mut shown: Vec<_> = players.sorted_indices(sort).into_iter()
+            .filter(|i| filters.shows(&players.player_list[*i], &user_filter, &comment_filter, &quick_filter, &self.flags))
+            .collect();
+        if filters.match_mode == MatchMode::Fuzzy && self.flags.sort_by_score && self.sort.column == SortColumn::Insertion {
+            // Best matches first, pinned players still first and ignored ones last. Stable, so equally good matches keep their order
+            shown.sort_by_key(|i| {
+                let player = &players.player_list[*i];
+                (player.ignored, !player.pinned, std::cmp::Reverse(filters.fuzzy_rank(player, &user_filter, &quick_filter)))
+            });
+        }
+        if self.flags.squad_first {
+            // Stable, so pinned players are still first in each group
+            shown.sort_by_key(|i| (players.player_list[*i].ignored, !players.player_list[*i].in_squad));
+        }
+
+        shown
+    }
+
+    /// For each player, whether "Delete filtered" would delete it: the ones shown in the table,
+    /// only if blank unless `include_commented`
+    fn filtered_deletions(&self, include_commented: bool) -> Vec<bool> {
+        let filters = &self.filters;
+        let user_filter = filters.normalize(&filters.user_filter_str);
+        let comment_filter = filters.normalize(&filters.comment_filter_str);
+        let quick_filter = filters.normalize(&filters.quick_filter_str);
+        self.players.player_list.iter()
+            .map(|player| filters.shows(player, &user_filter, &comment_filter, &quick_filter, &self.flags)
+                && (include_commented || player.is_blank()))
+            .collect()
+    }
+
+    /// Called when joining a squad. The players that are already in it are part of the session
+    fn start_session(&mut self) {
+        let players = self.players.player_list.iter()
+            .filter(|player| player.in_squad)
+            .map(|player| player.name.clone())
+            .collect();
+        self.sessions.push_back(SquadSession {
+            started_at: unix_now(),
+            ended_at: None,
+            context: self.context.clone(),
+            players,
+        });
+        while self.sessions.len() > MAX_SESSIONS {
+            self.sessions.pop_front();
+        }
+    }
+
+    /// Adds `username` to the current session, if there is one
+    fn add_to_session(&mut self, username: &str) {
+        if !self.self_in_squad {
+            return
+        }
+        if let Some(session) = self.sessions.back_mut().filter(|session| session.ended_at.is_none()) {
+            if !session.players.iter().any(|name| name.eq_ignore_ascii_case(username)) {
+                session.players.push(username.to_string())
+            }
+        }
+    }
+
+    fn end_session(&mut self) {
+        if let Some(session) = self.sessions.back_mut() {
+            session.ended_at.get_or_insert(unix_now());
+        }
+    }
+
+    /// Adds the most recently departed squad member to the list, so they can be commented on
+    fn add_last_leaver(&mut self) {
+        if let Some(username) = self.recently_left.pop_front() {
+            if self.players.get(&username).is_none() {
+                if let Err(e) = self.players.check_new_player(&username) {
+                    log(LogLevel::Warning, &format!("Not adding {username}: {e}"));
+                    return
+                }
+            }
+            let comment = self.new_player_comment();
+            self.players.add_player(&username, comment, true);
+        }
+    }
+
+    /// Whether `name` is the account playing, which is never in the list
+    fn is_self(&self, name: &str) -> bool {
+        !self.self_name.is_empty() && name.trim().eq_ignore_ascii_case(&self.self_name)
+    }
+
+    /// Whether there are unsaved changes and autosave_interval has passed since the last autosave
+    fn autosave_due(&self) -> bool {
+        self.autosave_interval != 0
+            && self.dirty
+            && self.last_autosave.elapsed() >= Duration::from_secs(self.autosave_interval as u64)
+    }
+
+    /// Renames like PlayerVecMap::rename(), checking `new_name` like the names added by hand
+    fn rename_player(&mut self, index: usize, username: &str, new_name: &str) -> Result<(), String> {
+        if self.is_self(new_name) {
+            return Err(format!("{new_name} is you, you can't be in the list"))
+        }
+        if !self.add_any_name && !is_account_name(new_name) {
+            return Err("Not an account name, e.g. Name.1234. Check \"Any name\" to allow it".to_string())
+        }
+        self.players.rename(index, username, new_name)
+    }
+
+    /// Called when the window is closed in the middle of editing a comment. imgui already wrote the text,
+    /// but makes sure the lowercase copy used by the filters matches and that the edit is autosaved
+    fn finish_comment_edit(&mut self) {
+        if let Some(username) = self.editing_comment.take() {
+            if let Some(player) = self.players.get_mut(&username) {
+                player.sync_lowercase_comment();
+            }
+            self.dirty = true;
+        }
+    }
+
+    /// Comment of players added by hand
+    fn new_player_comment(&self) -> String {
+        match self.flags.comment_met_date {
+            true => format_date(unix_now(), &self.met_date_format),
+            false => DEFAULT_COMMENT.to_string(),
+        }
+    }
+}
+
+/// Destructive actions that affect many players at once
+#[derive(Clone, Copy)]
+enum BulkAction {
+    ReloadConfig,
+    ClearAllComments,
+    RemoveUncommented,
+    /// Deletes the players shown in the table
+    DeleteFiltered { include_commented: bool },
+    MergeDuplicates,
+    /// Adds the names pasted in the options
+    AddPastedNames,
+}
+
+impl BulkAction {
+    fn description(&self, affected_players: usize) -> String {
+        match self {
+            BulkAction::ReloadConfig => format!("Reload the config from disk? Unsaved changes will be lost\nThis will affect {affected_players} players"),
+            BulkAction::ClearAllComments => format!("Clear the comments of every player?\nThis will affect {affected_players} players"),
+            BulkAction::RemoveUncommented => format!("You left the squad. Remove {affected_players} uncommented players?"),
+            BulkAction::DeleteFiltered { include_commented: true } => format!("Delete the {affected_players} players that match the filters, including their comments?"),
+            BulkAction::DeleteFiltered { include_commented: false } => format!("Delete the {affected_players} uncommented players that match the filters?"),
+            BulkAction::MergeDuplicates => format!("Merge players whose names only differ in capitalization?\n{affected_players} duplicates will be merged into the first of them"),
+            BulkAction::AddPastedNames => format!("Add the {affected_players} pasted names to the list?"),
+        }
+    }
+
+    /// Labels of the confirm and cancel buttons
+    fn button_labels(&self) -> (&'static str, &'static str) {
+        match self {
+            BulkAction::RemoveUncommented => ("Remove", "Keep"),
+            _ => ("Continue", "Cancel"),
+        }
+    }
+
+    fn affected_players(&self, state: &State) -> usize {
+        match self {
+            BulkAction::ReloadConfig => state.players.player_list.len(),
+            BulkAction::ClearAllComments => state.players.player_list.iter()
+                .filter(|player| !player.comment.is_empty())
+                .count(),
+            BulkAction::RemoveUncommented => state.players.player_list.iter()
+                .filter(|player| player.is_blank())
+                .count(),
+            BulkAction::DeleteFiltered { include_commented } => state.filtered_deletions(*include_commented).into_iter()
+                .filter(|deleted| *deleted)
+                .count(),
+            BulkAction::MergeDuplicates => state.players.duplicate_count(),
+            BulkAction::AddPastedNames => split_names(&state.paste_names).count(),
+        }
+    }
+}
+
+/// Modifier keys of the shortcuts
+#[derive(Clone, Copy, PartialEq)]
+struct Modifiers {
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl Modifiers {
+    const NONE: Modifiers = Modifiers { ctrl: false, alt: false, shift: false };
+    /// Alt+Shift, like the arcdps default
+    const DEFAULT: Modifiers = Modifiers { ctrl: false, alt: true, shift: true };
+
+    /// Updates the state of `key` if it's a modifier. Returns whether it is
+    fn update(&mut self, key: usize, key_down: bool) -> bool {
+        match key {
+            VK_SHIFT => self.shift = key_down,
+            VK_CONTROL => self.ctrl = key_down,
+            VK_MENU => self.alt = key_down,
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// e.g. "Alt+Shift+"
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for (held, name) in [(self.ctrl, "Ctrl+"), (self.alt, "Alt+"), (self.shift, "Shift+")] {
+            if held {
+                text.push_str(name)
+            }
+        }
+
+        text
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Shortcut {
+    ToggleWindow,
+    AddLastLeaver,
+    CyclePresets,
+    ToggleShowAll,
+    ClearFilters,
+    FocusAddUser,
+}
+
+impl Shortcut {
+    const ALL: [Shortcut; 6] = [Shortcut::ToggleWindow, Shortcut::AddLastLeaver, Shortcut::CyclePresets, Shortcut::ToggleShowAll, Shortcut::ClearFilters, Shortcut::FocusAddUser];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Shortcut::ToggleWindow => "Toggle window",
+            Shortcut::AddLastLeaver => "Add last leaver",
+            Shortcut::CyclePresets => "Cycle filter presets",
+            Shortcut::ToggleShowAll => "Toggle show all",
+            Shortcut::ClearFilters => "Clear filters",
+            Shortcut::FocusAddUser => "Add user",
+        }
+    }
+
+    fn id(&self) -> &'static str {
+        match self {
+            Shortcut::ToggleWindow => "toggle_window",
+            Shortcut::AddLastLeaver => "add_last_leaver",
+            Shortcut::CyclePresets => "cycle_presets",
+            Shortcut::ToggleShowAll => "toggle_show_all",
+            Shortcut::ClearFilters => "clear_filters",
+            Shortcut::FocusAddUser => "focus_add_user",
+        }
+    }
+
+    /// Key in the SHORTCUTS table
+    fn config_name(&self) -> &'static str {
+        match self {
+            Shortcut::ToggleWindow => "ToggleWindow",
+            Shortcut::AddLastLeaver => "AddLastLeaver",
+            Shortcut::CyclePresets => "CyclePresets",
+            Shortcut::ToggleShowAll => "ToggleShowAll",
+            Shortcut::ClearFilters => "ClearFilters",
+            Shortcut::FocusAddUser => "FocusAddUser",
+        }
+    }
+
+    /// Where the shortcut was stored before SHORTCUTS existed
+    fn legacy_config_name(&self) -> Option<&'static str> {
+        match self {
+            Shortcut::ToggleWindow => Some(SHORTCUT),
+            Shortcut::AddLastLeaver => Some(ADD_LEAVER_SHORTCUT),
+            Shortcut::CyclePresets => Some(CYCLE_PRESETS_SHORTCUT),
+            _ => None,
+        }
+    }
+}
+
+static STATE: Lazy<Mutex<State>> = Lazy::new(|| Mutex::new(State::new()));
+/// Generation of the last config written, locked while writing one. Autosaves are written
+/// from other threads, so an older config could otherwise overwrite a newer one
+static SAVED_GENERATION: Mutex<u64> = Mutex::new(0);
+static NEXT_SAVE_GENERATION: AtomicU64 = AtomicU64::new(1);
+/// The backups are rotated on the first save of the session only. Otherwise autosaves would
+/// replace all of them with the same bad change shortly after it was made
+static BACKUPS_ROTATED: AtomicBool = AtomicBool::new(false);
+/// Default of config_path()
+const CONFIG_PATH: &'static str = "addons/arcdps/player_list.toml";
+/// Overrides CONFIG_PATH, e.g. to keep several profiles
+const CONFIG_PATH_VAR: &'static str = "GW2_PLAYER_LIST_CONFIG";
+/// The config is written to a file with this extension first, and then moved to config_path()
+const TMP_EXTENSION: &'static str = "tmp";
+const LOG_PATH: &'static str = "addons/arcdps/player_list.log";
+/// The previous log, once LOG_PATH is bigger than LOG_MAX_SIZE bytes
+const OLD_LOG_PATH: &'static str = "addons/arcdps/player_list.old.log";
+const LOG_MAX_SIZE: u64 = 1024 * 1024;
+/// The configs of previous sessions are kept in player_list.bak.1 (newest) to player_list.bak.{BACKUP_COUNT}
+const BACKUP_EXTENSION: &'static str = "bak";
+const BACKUP_COUNT: usize = 3;
+/// Unreadable configs are moved to player_list.broken.{unix time}.toml
+const BROKEN_EXTENSION: &'static str = "broken";
+
+const PLAYERS: &'static str = "Players";
+const OPENED_WINDOW: &'static str = "WindowOpen";
+/// OPENED_WINDOW of each account
+const PROFILE_OPENED_WINDOW: &'static str = "ProfileWindowOpen";
+const INACTIVE_COLOR: &'static str = "InactiveColor";
+const SHOW_ALL: &'static str = "ShowAll";
+const COMMENTED_ONLY: &'static str = "CommentedOnly";
+const SHOW_IGNORED: &'static str = "ShowIgnored";
+const AUTO_ADD: &'static str = "AutoAdd";
+const APPEND_COMMENT_ON_ADD: &'static str = "AppendCommentOnAdd";
+const FADE_ON_LEAVE: &'static str = "FadeOnLeave";
+const PRETTY_CONFIG: &'static str = "PrettyConfig";
+const SORT_SAVED_PLAYERS: &'static str = "SortSavedPlayers";
+const CONFIRM_BULK_ACTIONS: &'static str = "ConfirmBulkActions";
+const CONFIRM_WIPE: &'static str = "ConfirmWipe";
+const WIPE_CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
+const FILE_QUERY: &'static str = "FileQuery";
+const SELECT_ALL_ON_EDIT: &'static str = "SelectAllOnEdit";
+const AUTO_SIZE_COMMENTS: &'static str = "AutoSizeComments";
+const COMMENT_MET_DATE: &'static str = "CommentMetDate";
+const MET_DATE_FORMAT: &'static str = "MetDateFormat";
+const DEFAULT_MET_DATE_FORMAT: &'static str = "met {year}-{month}-{day}";
+const SORT_BY_SCORE: &'static str = "SortByScore";
+const FILTER_MODE: &'static str = "FilterMode";
+const CASE_SENSITIVE: &'static str = "CaseSensitive";
+const SHOW_ENCOUNTERS: &'static str = "ShowEncounters";
+const SHOW_RATING: &'static str = "ShowRating";
+const SHOW_PROFESSION: &'static str = "ShowProfession";
+const SHOW_SUBGROUP: &'static str = "ShowSubgroup";
+const SHOW_CHAT: &'static str = "ShowChat";
+const EXPORT_PATH: &'static str = "addons/arcdps/player_list_export.csv";
+const IMPORT_PATH: &'static str = "addons/arcdps/player_list_import.csv";
+/// Both exported to and imported from
+const JSON_PATH: &'static str = "addons/arcdps/player_list.json";
+/// Only exported to, with the players shown in the table
+const VISIBLE_EXPORT_PATH: &'static str = "addons/arcdps/player_list_visible.csv";
+const VISIBLE_JSON_PATH: &'static str = "addons/arcdps/player_list_visible.json";
+const IMPORT_OVERWRITE: &'static str = "ImportOverwrite";
+const KEEP_ALL: &'static str = "KeepAll";
+const KEEP_ALL_LIMIT: &'static str = "KeepAllLimit";
+const DEFAULT_KEEP_ALL_LIMIT: u32 = 2000;
+const MAX_PLAYERS: &'static str = "MaxPlayers";
+const DEFAULT_MAX_PLAYERS: u32 = 10000;
+/// Of names added by hand. Account names are much shorter, this only stops pasting whole texts as a name
+const MAX_NAME_LEN: usize = 64;
+/// Pasting more names than this into "Add all" asks for confirmation, even if confirm_bulk_actions is off
+const PASTE_CONFIRM_COUNT: usize = 50;
+const ROSTER_PATH: &'static str = "addons/arcdps/player_list_roster.json";
+/// Written next to ROSTER_PATH and renamed over it, so readers never see it half-written
+const ROSTER_TMP_PATH: &'static str = "addons/arcdps/player_list_roster.json.tmp";
+/// Of the format of ROSTER_PATH. Increased when fields are removed or change meaning, not when they are added
+const ROSTER_VERSION: u32 = 1;
+const QUERY_PATH: &'static str = "addons/arcdps/player_list_query.txt";
+const QUERY_RESPONSE_PATH: &'static str = "addons/arcdps/player_list_query_response.json";
+const FILE_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+const FADE_DURATION: Duration = Duration::from_secs(5);
+/// How long the chat column shows that a player wrote in the squad chat
+const CHAT_INDICATOR_DURATION: Duration = Duration::from_secs(15);
+/// Of the chat indicator, fading out over CHAT_INDICATOR_DURATION
+const CHAT_COLOR: [f32;4] = [0.4,0.9,0.4,1.0];
+const COMMENT_SIZE: &'static str = "CommentSize";
+const COMMENT_MAX_LEN: &'static str = "CommentMaxLen";
+const SNIPPETS: &'static str = "Snippets";
+const AUTOSAVE_INTERVAL: &'static str = "AutosaveInterval";
+const ROW_TINT_ALPHA: &'static str = "RowTintAlpha";
+/// Low enough for the text to stay readable
+const DEFAULT_ROW_TINT_ALPHA: f32 = 0.15;
+/// Row background of pinned players without a color, before applying row_tint_alpha
+const PINNED_ROW_COLOR: [f32;4] = [1.0,0.8,0.0,1.0];
+const DEFAULT_AUTOSAVE_INTERVAL: u32 = 300;
+const PROFILE_URL: &'static str = "ProfileUrl";
+const DEFAULT_PROFILE_URL: &'static str = "https://wiki.guildwars2.com/index.php?search={name}";
+const WINDOW_POS: &'static str = "WindowPos";
+const WINDOW_SIZE: &'static str = "WindowSize";
+const DEFAULT_INACTIVE_COLOR: [f32;4] = [0.5,0.5,0.5,1.0];
+/// Of the part of names that matches the filter
+const HIGHLIGHT_COLOR: [f32;4] = [1.0,0.85,0.2,1.0];
+/// Of the commander marker, like the commander tag in game
+const COMMANDER_COLOR: [f32;4] = [0.3,0.6,1.0,1.0];
+/// Of the names of ignored players, dimmer than the default inactive color
+const IGNORED_COLOR: [f32;4] = [0.45,0.3,0.3,1.0];
+const MAX_RATING: u8 = 5;
+/// Of the stars of the rating
+const STAR_COLOR: [f32;4] = [1.0,0.8,0.0,1.0];
+/// Background of the row selected with the arrow keys
+const SELECTED_ROW_COLOR: [f32;4] = [0.3,0.3,0.5,0.5];
+/// Of input validation errors
+const ERROR_COLOR: [f32;4] = [1.0,0.4,0.4,1.0];
+const DEFAULT_COMMENT_SIZE: [f32;2] = [300.0, 20.0];
+const COMMENT_SIZE_MIN: [f32;2] = [20.0, 10.0];
+const COMMENT_SIZE_MAX: [f32;2] = [4000.0, 2000.0];
+const COLUMN_SPLIT: &'static str = "ColumnSplit";
+/// Same width for the name and the comment
+const DEFAULT_COLUMN_SPLIT: f32 = 0.5;
+/// So neither column can be dragged to nothing and lost
+const COLUMN_SPLIT_MIN: f32 = 0.05;
+const COLUMN_SPLIT_MAX: f32 = 0.95;
+const SHORTCUTS: &'static str = "Shortcuts";
+// Only read, for compatibility with configs from before SHORTCUTS
+const SHORTCUT: &'static str = "ShortcutKey";
+const ADD_LEAVER_SHORTCUT: &'static str = "AddLeaverShortcutKey";
+const CYCLE_PRESETS_SHORTCUT: &'static str = "CyclePresetsShortcutKey";
+const DEFAULT_SORT: &'static str = "DefaultSort";
+const DEFAULT_SORT_ASCENDING: &'static str = "DefaultSortAscending";
+const SORT: &'static str = "Sort";
+const SORT_ASCENDING: &'static str = "SortAscending";
+/// Start with the last used sort instead of DEFAULT_SORT
+const REMEMBER_SORT: &'static str = "RememberSort";
+const SORT_ALPHABETICAL: &'static str = "SortAlphabetical";
+const SQUAD_FIRST: &'static str = "SquadFirst";
+const HIGHLIGHT_MATCHES: &'static str = "HighlightMatches";
+const HIDE_DISCRIMINATOR: &'static str = "HideDiscriminator";
+const COMPACT: &'static str = "Compact";
+const SHORTCUT_CTRL: &'static str = "ShortcutCtrl";
+const SHORTCUT_ALT: &'static str = "ShortcutAlt";
+const SHORTCUT_SHIFT: &'static str = "ShortcutShift";
+const VK_SHIFT: usize = 0x10;
+const VK_CONTROL: usize = 0x11;
+const VK_MENU: usize = 0x12;
+const FILTER_PRESETS: &'static str = "FilterPresets";
+const SESSIONS: &'static str = "Sessions";
+/// How many past squads are remembered
+const MAX_SESSIONS: usize = 20;
+const DEFAULT_COMMENT: &'static str = "Comment here";
+/// How many players that left the squad are remembered
+const RECENTLY_LEFT_CAPACITY: usize = 10;
+
+fn init() -> Result<(), String> {
+    // May return an error to indicate load failure
+
+    let mut state = get_state();
+    let config_path = config_path();
+    let config = match read_config(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            log(LogLevel::Error, &e);
+            // The next save would overwrite the comments in it, so it's kept aside for manual recovery
+            let broken_path = format!("{}.{}.toml", config_sibling_path(BROKEN_EXTENSION), unix_now());
+            match std::fs::rename(&config_path, &broken_path) {
+                Ok(()) => log(LogLevel::Info, &format!("Moved it to {broken_path}")),
+                Err(rename_error) => {
+                    log(LogLevel::Error, &format!("Couldn't move it to {broken_path}: {rename_error}. It won't be saved over"));
+                    state.flags.save_disabled = true;
+                }
+            }
+            state.options_status = Some(e);
+            read_backup().unwrap_or_default()
+        }
     };
-    let show_all = match config.remove(SHOW_ALL) {
-        Some(Value::Boolean(b)) => b,
-        _ => false,
+    load_config(&mut state, config);
+
+    Ok(())
+}
+
+/// Reads and parses the config file. A missing file is read as an empty config
+fn read_config(path: &str) -> Result<Map<String, Value>, String> {
+    let toml_string = match std::fs::read_to_string(path) {
+        Ok(toml_string) => toml_string,
+        // A missing config is expected on the first run
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Couldn't read {path}: {e}")),
     };
 
-    let shortcut_char = match config.remove(SHORTCUT) {
-        Some(Value::String(s)) => { // For compatibility with 0.1.2
-            if s.len() == 1 {
-                let c = s.chars()
-                    .next()
-                    .filter(|c| ('A'..='Z').contains(c));
-                match c {
-                    Some(c) => match c {
-                        'A' => Some(VirtualKey::A),
-                        'B' => Some(VirtualKey::B),
-                        'C' => Some(VirtualKey::C),
-                        'D' => Some(VirtualKey::D),
-                        'E' => Some(VirtualKey::E),
-                        'F' => Some(VirtualKey::F),
-                        'G' => Some(VirtualKey::G),
-                        'H' => Some(VirtualKey::H),
-                        'I' => Some(VirtualKey::I),
-                        'J' => Some(VirtualKey::J),
-                        'K' => Some(VirtualKey::K),
-                        'L' => Some(VirtualKey::L),
-                        'M' => Some(VirtualKey::M),
-                        'N' => Some(VirtualKey::N),
-                        'O' => Some(VirtualKey::O),
-                        'P' => Some(VirtualKey::P),
-                        'Q' => Some(VirtualKey::Q),
-                        'R' => Some(VirtualKey::R),
-                        'S' => Some(VirtualKey::S),
-                        'T' => Some(VirtualKey::T),
-                        'U' => Some(VirtualKey::U),
-                        'V' => Some(VirtualKey::V),
-                        'W' => Some(VirtualKey::W),
-                        'X' => Some(VirtualKey::X),
-                        'Y' => Some(VirtualKey::Y),
-                        'Z' => Some(VirtualKey::Z),
-                        _ => None
-                    },
-                    None => None,
-                }
-            } else {
-                None
-            }
-        },
-        Some(Value::Integer(i)) => {
-            Some(VirtualKey(i as i32))
+    match toml::from_str::<Value>(&toml_string) {
+        Ok(Value::Table(config)) => Ok(config),
+        Ok(_) => Err(format!("{path} is not a table")),
+        Err(e) => Err(format!("Couldn't parse {path}: {e}")),
+    }
+}
+
+/// The most recent backup that can be read
+fn read_backup() -> Option<Map<String, Value>> {
+    for n in 1..=BACKUP_COUNT {
+        let path = backup_path(n);
+        if !Path::new(&path).exists() {
+            continue
+        }
+        match read_config(&path) {
+            Ok(config) => {
+                log(LogLevel::Info, &format!("Loaded the backup {path}"));
+                return Some(config)
+            },
+            Err(e) => log(LogLevel::Warning, &e),
+        }
+    }
+
+    None
+}
+
+/// CONFIG_PATH, unless overridden with the CONFIG_PATH_VAR environment variable
+fn config_path() -> String {
+    std::env::var(CONFIG_PATH_VAR).unwrap_or(CONFIG_PATH.to_string())
+}
+
+/// config_path() with its extension replaced by `extension`
+fn config_sibling_path(extension: &str) -> String {
+    Path::new(&config_path()).with_extension(extension).to_string_lossy().into_owned()
+}
+
+fn backup_path(n: usize) -> String {
+    format!("{}.{n}", config_sibling_path(BACKUP_EXTENSION))
+}
+
+/// Shifts the backups (dropping the oldest) and copies the config to the newest one
+fn rotate_backups() -> std::io::Result<()> {
+    let config_path = config_path();
+    if !Path::new(&config_path).exists() {
+        return Ok(())
+    }
+    for n in (1..BACKUP_COUNT).rev() {
+        match std::fs::rename(backup_path(n), backup_path(n + 1)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+    }
+
+    std::fs::copy(config_path, backup_path(1)).map(|_| ())
+}
+
+fn load_config(state: &mut State, mut config: Map<String, Value>) {
+    let player_list = init_player_list(&mut config);
+    let profile_display_window = match config.remove(PROFILE_OPENED_WINDOW) {
+        Some(Value::Table(profiles)) => profiles.into_iter()
+            .filter_map(|(profile, open)| match open {
+                Value::Boolean(open) => Some((profile, open)),
+                _ => None
+            }).collect(),
+        _ => HashMap::new(),
+    };
+    let display_window = match config.remove(OPENED_WINDOW) {
+        Some(Value::Boolean(b)) => b,
+        _ => false,
+    };
+    let inactive_color = read_color(config.remove(INACTIVE_COLOR)).unwrap_or(DEFAULT_INACTIVE_COLOR);
+    let comment_size = match config.remove(COMMENT_SIZE) {
+        Some(Value::Array(mut arr)) => {
+            if arr.len() == 2 {
+                let h = arr.remove(1);
+                let w = arr.remove(0);
+                if let (Value::Float(w), Value::Float(h)) = (w, h) {
+                    clamp_comment_size([w as f32,h as f32])
+                } else {
+                    DEFAULT_COMMENT_SIZE
+                }
+            } else {
+                DEFAULT_COMMENT_SIZE
+            }
+        },
+        _ => DEFAULT_COMMENT_SIZE,
+    };
+    let comment_max_len = match config.remove(COMMENT_MAX_LEN) {
+        Some(Value::Integer(max_len)) => max_len.clamp(0, u32::MAX as i64) as u32,
+        _ => 0,
+    };
+    let row_tint_alpha = match config.remove(ROW_TINT_ALPHA) {
+        Some(Value::Float(alpha)) if !alpha.is_nan() => alpha.clamp(0.0, 1.0) as f32,
+        _ => DEFAULT_ROW_TINT_ALPHA,
+    };
+    let column_split = match config.remove(COLUMN_SPLIT) {
+        Some(Value::Float(split)) => clamp_column_split(split as f32),
+        _ => DEFAULT_COLUMN_SPLIT,
+    };
+    let snippets = string_array(config.remove(SNIPPETS));
+    let autosave_interval = match config.remove(AUTOSAVE_INTERVAL) {
+        Some(Value::Integer(interval)) => interval.clamp(0, u32::MAX as i64) as u32,
+        _ => DEFAULT_AUTOSAVE_INTERVAL,
+    };
+    let profile_url = match config.remove(PROFILE_URL) {
+        Some(Value::String(profile_url)) => profile_url,
+        _ => DEFAULT_PROFILE_URL.to_string(),
+    };
+    let met_date_format = match config.remove(MET_DATE_FORMAT) {
+        Some(Value::String(met_date_format)) => met_date_format,
+        _ => DEFAULT_MET_DATE_FORMAT.to_string(),
+    };
+    let window_pos = read_vec2(config.remove(WINDOW_POS));
+    let window_size = read_vec2(config.remove(WINDOW_SIZE));
+    let show_all = match config.remove(SHOW_ALL) {
+        Some(Value::Boolean(b)) => b,
+        _ => false,
+    };
+    let commented_only = read_bool(config.remove(COMMENTED_ONLY), false);
+    let show_ignored = read_bool(config.remove(SHOW_IGNORED), true);
+    let auto_add = read_bool(config.remove(AUTO_ADD), true);
+    let append_comment_on_add = read_bool(config.remove(APPEND_COMMENT_ON_ADD), false);
+    let fade_on_leave = read_bool(config.remove(FADE_ON_LEAVE), false);
+    let pretty_config = read_bool(config.remove(PRETTY_CONFIG), false);
+    let sort_saved_players = read_bool(config.remove(SORT_SAVED_PLAYERS), false);
+    let confirm_bulk_actions = read_bool(config.remove(CONFIRM_BULK_ACTIONS), true);
+    let confirm_wipe = read_bool(config.remove(CONFIRM_WIPE), false);
+    let file_query = read_bool(config.remove(FILE_QUERY), false);
+    let select_all_on_edit = read_bool(config.remove(SELECT_ALL_ON_EDIT), false);
+    let auto_size_comments = read_bool(config.remove(AUTO_SIZE_COMMENTS), false);
+    let comment_met_date = read_bool(config.remove(COMMENT_MET_DATE), false);
+    let sort_by_score = read_bool(config.remove(SORT_BY_SCORE), true);
+    let case_sensitive = read_bool(config.remove(CASE_SENSITIVE), false);
+    let show_encounters = read_bool(config.remove(SHOW_ENCOUNTERS), false);
+    let show_rating = read_bool(config.remove(SHOW_RATING), true);
+    let show_profession = read_bool(config.remove(SHOW_PROFESSION), true);
+    let show_subgroup = read_bool(config.remove(SHOW_SUBGROUP), true);
+    let show_chat = read_bool(config.remove(SHOW_CHAT), false);
+    let import_overwrite = read_bool(config.remove(IMPORT_OVERWRITE), false);
+    let keep_all = read_bool(config.remove(KEEP_ALL), false);
+    let keep_all_limit = match config.remove(KEEP_ALL_LIMIT) {
+        Some(Value::Integer(limit)) => limit.clamp(0, u32::MAX as i64) as u32,
+        _ => DEFAULT_KEEP_ALL_LIMIT,
+    };
+    let max_players = match config.remove(MAX_PLAYERS) {
+        Some(Value::Integer(max)) => max.clamp(0, u32::MAX as i64) as u32,
+        _ => DEFAULT_MAX_PLAYERS,
+    };
+    let filter_mode = match config.remove(FILTER_MODE) {
+        Some(Value::String(s)) => MatchMode::from_config_name(&s).unwrap_or(MatchMode::Contains),
+        _ => MatchMode::Contains,
+    };
+
+    let shortcut_keys = init_shortcuts(&mut config);
+    let shortcut_modifiers = Modifiers {
+        ctrl: read_bool(config.remove(SHORTCUT_CTRL), Modifiers::DEFAULT.ctrl),
+        alt: read_bool(config.remove(SHORTCUT_ALT), Modifiers::DEFAULT.alt),
+        shift: read_bool(config.remove(SHORTCUT_SHIFT), Modifiers::DEFAULT.shift),
+    };
+    let filter_presets = init_filter_presets(&mut config);
+    let sessions = match config.remove(SESSIONS) {
+        Some(Value::Array(sessions)) => sessions.into_iter().filter_map(SquadSession::from_toml).collect(),
+        _ => VecDeque::new(),
+    };
+
+    let default_sort = Sort::from_toml(config.remove(DEFAULT_SORT), config.remove(DEFAULT_SORT_ASCENDING))
+        .unwrap_or(Sort::new());
+    let remember_sort = read_bool(config.remove(REMEMBER_SORT), true);
+    let sort_alphabetical = read_bool(config.remove(SORT_ALPHABETICAL), false);
+    let squad_first = read_bool(config.remove(SQUAD_FIRST), false);
+    let highlight_matches = read_bool(config.remove(HIGHLIGHT_MATCHES), true);
+    let hide_discriminator = read_bool(config.remove(HIDE_DISCRIMINATOR), false);
+    let compact = read_bool(config.remove(COMPACT), false);
+    let last_sort = Sort::from_toml(config.remove(SORT), config.remove(SORT_ASCENDING));
+    let sort = match last_sort {
+        Some(last_sort) if remember_sort => last_sort,
+        _ => default_sort,
+    };
+
+    state.players = player_list;
+    state.players.keep_all = keep_all;
+    state.players.keep_all_limit = keep_all_limit;
+    state.players.max_players = max_players;
+    state.flags.display_window = display_window;
+    state.profile_display_window = profile_display_window;
+    state.apply_profile_display_window();
+    state.flags.show_all = show_all;
+    state.flags.commented_only = commented_only;
+    state.flags.show_ignored = show_ignored;
+    state.flags.auto_add = auto_add;
+    state.flags.append_comment_on_add = append_comment_on_add;
+    state.flags.fade_on_leave = fade_on_leave;
+    state.flags.pretty_config = pretty_config;
+    state.flags.sort_saved_players = sort_saved_players;
+    state.flags.confirm_bulk_actions = confirm_bulk_actions;
+    state.flags.confirm_wipe = confirm_wipe;
+    state.flags.file_query = file_query;
+    state.flags.select_all_on_edit = select_all_on_edit;
+    state.flags.auto_size_comments = auto_size_comments;
+    state.flags.comment_met_date = comment_met_date;
+    state.flags.sort_by_score = sort_by_score;
+    state.filters.match_mode = filter_mode;
+    state.filters.case_sensitive = case_sensitive;
+    state.flags.show_encounters = show_encounters;
+    state.flags.show_rating = show_rating;
+    state.flags.show_profession = show_profession;
+    state.flags.show_subgroup = show_subgroup;
+    state.flags.show_chat = show_chat;
+    state.flags.import_overwrite = import_overwrite;
+    state.inactive_color = inactive_color;
+    state.comment_size = comment_size;
+    state.comment_max_len = comment_max_len;
+    state.row_tint_alpha = row_tint_alpha;
+    state.column_split = column_split;
+    state.snippets = snippets;
+    state.autosave_interval = autosave_interval;
+    state.profile_url = profile_url;
+    state.met_date_format = met_date_format;
+    state.window_pos = window_pos;
+    state.window_size = window_size;
+    state.shortcut_keys = shortcut_keys;
+    state.shortcut_modifiers = shortcut_modifiers;
+    state.filter_presets = filter_presets;
+    state.sessions = sessions;
+    state.current_preset = None;
+    state.default_sort = default_sort;
+    state.flags.remember_sort = remember_sort;
+    state.flags.sort_alphabetical = sort_alphabetical;
+    state.flags.squad_first = squad_first;
+    state.flags.highlight_matches = highlight_matches;
+    state.flags.hide_discriminator = hide_discriminator;
+    state.flags.compact = compact;
+    state.sort = sort;
+}
+
+/// Replaces the state with the contents of the config file, keeping track of who is in the squad.
+/// On error the state is left untouched
+fn reload_config(state: &mut State) -> Result<(), String> {
+    let config = read_config(&config_path())?;
+
+    // What isn't saved in the config, copied back as is instead of joining again, which would count
+    // another encounter and restart the time in squad
+    let runtime: Vec<_> = state.players.player_list.iter()
+        .filter(|player| player.in_squad || player.left_at.is_some() || player.last_chat.is_some())
+        .map(|player| (player.name.clone(), player.in_squad, player.joined_at, player.subgroup, player.is_commander, player.last_chat, player.left_at))
+        .collect();
+    let display_window = state.flags.display_window;
+
+    load_config(state, config);
+    // It can be read now, so saving over it is safe
+    state.flags.save_disabled = false;
+
+    for (username, in_squad, joined_at, subgroup, is_commander, last_chat, left_at) in runtime {
+        if state.players.get(&username).is_none() {
+            // Squad members that weren't saved yet, only if they would have been added by joining
+            if !in_squad || !state.flags.auto_add {
+                continue
+            }
+            state.players.insert(Player::new(&username, String::new(), false));
+        }
+        if let Some(player) = state.players.get_mut(&username) {
+            player.in_squad = in_squad;
+            player.joined_at = joined_at;
+            player.subgroup = subgroup;
+            player.is_commander = is_commander;
+            player.last_chat = last_chat;
+            player.left_at = left_at;
+        }
+    }
+    state.flags.display_window = display_window;
+
+    Ok(())
+}
+
+fn init_shortcuts(config: &mut Map<String, Value>) -> HashMap<Shortcut, VirtualKey> {
+    let mut shortcuts = match config.remove(SHORTCUTS) {
+        Some(Value::Table(shortcuts)) => shortcuts,
+        _ => Map::new(),
+    };
+    let mut shortcut_keys = HashMap::new();
+    for shortcut in Shortcut::ALL {
+        let legacy_value = shortcut.legacy_config_name().and_then(|name| config.remove(name));
+        let value = shortcuts.remove(shortcut.config_name()).or(legacy_value);
+        if let Some(key) = parse_shortcut(value) {
+            shortcut_keys.insert(shortcut, key);
+        }
+    }
+
+    shortcut_keys
+}
+
+fn parse_shortcut(value: Option<Value>) -> Option<VirtualKey> {
+    match value {
+        Some(Value::String(s)) => { // For compatibility with 0.1.2
+            if s.len() == 1 {
+                let c = s.chars()
+                    .next()
+                    .filter(|c| ('A'..='Z').contains(c));
+                match c {
+                    Some(c) => match c {
+                        'A' => Some(VirtualKey::A),
+                        'B' => Some(VirtualKey::B),
+                        'C' => Some(VirtualKey::C),
+                        'D' => Some(VirtualKey::D),
+                        'E' => Some(VirtualKey::E),
+                        'F' => Some(VirtualKey::F),
+                        'G' => Some(VirtualKey::G),
+                        'H' => Some(VirtualKey::H),
+                        'I' => Some(VirtualKey::I),
+                        'J' => Some(VirtualKey::J),
+                        'K' => Some(VirtualKey::K),
+                        'L' => Some(VirtualKey::L),
+                        'M' => Some(VirtualKey::M),
+                        'N' => Some(VirtualKey::N),
+                        'O' => Some(VirtualKey::O),
+                        'P' => Some(VirtualKey::P),
+                        'Q' => Some(VirtualKey::Q),
+                        'R' => Some(VirtualKey::R),
+                        'S' => Some(VirtualKey::S),
+                        'T' => Some(VirtualKey::T),
+                        'U' => Some(VirtualKey::U),
+                        'V' => Some(VirtualKey::V),
+                        'W' => Some(VirtualKey::W),
+                        'X' => Some(VirtualKey::X),
+                        'Y' => Some(VirtualKey::Y),
+                        'Z' => Some(VirtualKey::Z),
+                        _ => None
+                    },
+                    None => None,
+                }
+            } else {
+                None
+            }
+        },
+        Some(Value::Integer(i)) => {
+            Some(VirtualKey(i as i32))
+        }
+        _ => None
+    }
+}
+
+fn init_extras(_: ExtrasAddonInfo, self_name: Option<&str>) {
+    let mut state = get_state();
+
+    if let Some(self_name) = self_name {
+        state.flags.extras_initialized = true;
+        state.self_name = self_name.to_owned();
+        state.apply_profile_display_window();
+    }
+}
+
+fn init_player_list(config: &mut Map<String, Value>) -> PlayerVecMap {
+    let players = config.remove(PLAYERS);
+
+    let players = match players {
+        Some(Value::Array(players)) => players,
+        _ => vec![],
+    };
+
+    let mut player_map = HashMap::new();
+
+    let player_list: Vec<_> = players.into_iter().filter_map(player_from_toml).collect();
+
+    // Duplicates from before names were case insensitive point at the last of them, until merged
+    for (i, player) in player_list.iter().enumerate() {
+        player_map.insert(player.lowercase_name.clone(), i);
+    }
+
+    PlayerVecMap {
+        player_list,
+        name_dict: player_map,
+        last_deleted: Vec::new(),
+        keep_all: false,
+        keep_all_limit: DEFAULT_KEEP_ALL_LIMIT,
+        max_players: DEFAULT_MAX_PLAYERS,
+    }
+}
+
+/// Reads a player written by Player::to_toml(). None if it has no name or comment
+fn player_from_toml(val: Value) -> Option<Player> {
+    let mut properties = match val {
+        Value::Table(properties) => properties,
+        _ => return None
+    };
+
+    let name = properties.remove("name");
+    let comment = properties.remove("comment");
+    let contexts = string_array(properties.remove("contexts"));
+    let labels = string_array(properties.remove("labels"));
+    let last_seen = match properties.remove("last_seen") {
+        Some(Value::Integer(last_seen)) => Some(last_seen),
+        _ => None
+    };
+    let first_met = match properties.remove("first_met") {
+        Some(Value::Integer(first_met)) => Some(first_met),
+        _ => None
+    };
+    let color = read_color(properties.remove("color"));
+    let profession = match properties.remove("profession") {
+        Some(Value::String(profession)) => Some(profession),
+        _ => None
+    };
+    let pinned = read_bool(properties.remove("pinned"), false);
+    let ignored = read_bool(properties.remove("ignored"), false);
+    let added_manually = read_bool(properties.remove("added_manually"), false);
+    let rating = match properties.remove("rating") {
+        Some(Value::Integer(rating)) => rating.clamp(0, MAX_RATING as i64) as u8,
+        _ => 0
+    };
+    let encounter_count = match properties.remove("encounters") {
+        Some(Value::Integer(encounters)) => encounters.clamp(0, u32::MAX as i64) as u32,
+        _ => 0
+    };
+
+    if let (Some(Value::String(name)), Some(Value::String(comment))) = (name, comment) {
+        Some(Player {
+            lowercase_name: name.to_lowercase(),
+            name,
+            lowercase_comment: comment.to_lowercase(),
+            comment,
+            in_squad: false,
+            contexts,
+            labels,
+            last_seen,
+            first_met,
+            encounter_count,
+            color,
+            profession,
+            subgroup: None,
+            is_commander: false,
+            pinned,
+            added_manually,
+            rating,
+            ignored,
+            left_at: None,
+            joined_at: None,
+            last_chat: None,
+        })
+    } else {
+        None
+    }
+}
+
+fn init_filter_presets(config: &mut Map<String, Value>) -> Vec<FilterPreset> {
+    let presets = match config.remove(FILTER_PRESETS) {
+        Some(Value::Array(presets)) => presets,
+        _ => vec![],
+    };
+
+    presets.into_iter()
+        .filter_map(|val| {
+            let mut properties = match val {
+                Value::Table(properties) => properties,
+                _ => return None
+            };
+
+            match properties.remove("name") {
+                Some(Value::String(name)) => Some(FilterPreset {
+                    name,
+                    filters: Filters::from_toml(&mut properties),
+                }),
+                _ => None
+            }
+        }).collect()
+}
+
+/// None if `value` isn't an array of exactly 2 floats
+fn read_vec2(value: Option<Value>) -> Option<[f32;2]> {
+    match value {
+        Some(Value::Array(arr)) => match arr.as_slice() {
+            [Value::Float(x), Value::Float(y)] => Some([*x as f32, *y as f32]),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// None if it's not 4 numbers. Components outside of 0..1 are clamped
+fn read_color(value: Option<Value>) -> Option<[f32;4]> {
+    let color = match value {
+        Some(Value::Array(arr)) => match arr.as_slice() {
+            [Value::Float(r), Value::Float(g), Value::Float(b), Value::Float(a)] => [*r as f32, *g as f32, *b as f32, *a as f32],
+            _ => return None
+        },
+        _ => return None
+    };
+    clamp_color(color)
+}
+
+/// None if a component is NaN
+fn clamp_color(color: [f32;4]) -> Option<[f32;4]> {
+    if color.iter().any(|val| val.is_nan()) {
+        return None
+    }
+    Some(color.map(|val| val.clamp(0.0, 1.0)))
+}
+
+fn color_to_toml(color: [f32;4]) -> Value {
+    // NaN.clamp() is NaN, which can't be read back
+    let color = color.map(|val| if val.is_nan() { 0.0 } else { val.clamp(0.0, 1.0) });
+    Value::Array(color.into_iter().map(|val| Value::Float(val as f64)).collect())
+}
+
+/// Keeps the split between COLUMN_SPLIT_MIN and COLUMN_SPLIT_MAX. Not a number becomes DEFAULT_COLUMN_SPLIT
+fn clamp_column_split(split: f32) -> f32 {
+    if split.is_nan() {
+        return DEFAULT_COLUMN_SPLIT
+    }
+    split.clamp(COLUMN_SPLIT_MIN, COLUMN_SPLIT_MAX)
+}
+
+/// Keeps the size between COMMENT_SIZE_MIN and COMMENT_SIZE_MAX. Not a number becomes DEFAULT_COMMENT_SIZE
+fn clamp_comment_size(size: [f32;2]) -> [f32;2] {
+    if size.iter().any(|val| val.is_nan()) {
+        return DEFAULT_COMMENT_SIZE
+    }
+    [
+        size[0].clamp(COMMENT_SIZE_MIN[0], COMMENT_SIZE_MAX[0]),
+        size[1].clamp(COMMENT_SIZE_MIN[1], COMMENT_SIZE_MAX[1]),
+    ]
+}
+
+fn read_bool(value: Option<Value>, default: bool) -> bool {
+    match value {
+        Some(Value::Boolean(b)) => b,
+        _ => default,
+    }
+}
+
+fn string_array(value: Option<Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(arr)) => arr.into_iter()
+            .filter_map(|val| match val {
+                Value::String(s) => Some(s),
+                _ => None
+            }).collect(),
+        _ => Vec::new()
+    }
+}
+
+fn release() {
+    let mut state = get_state();
+    // The DLL is about to be unloaded, so an autosave still writing must finish first
+    if let Some(thread) = state.autosave_thread.take() {
+        let _ = thread.join();
+    }
+    if state.flags.save_disabled {
+        log(LogLevel::Warning, &format!("Not saving, to avoid overwriting {}", config_path()));
+        return
+    }
+    if let Some((generation, toml_string)) = serialize_config(&state) {
+        save_config(generation, &toml_string);
+    }
+}
+
+/// Saves in another thread if the state is dirty and autosave_interval has passed since the last autosave
+fn autosave(state: &mut State) {
+    if state.autosave_interval == 0 || state.flags.save_disabled {
+        return
+    }
+    // Only one at a time. If the last one failed, the changes it had still have to be saved
+    if let Some(thread) = &state.autosave_thread {
+        if !thread.is_finished() {
+            return
+        }
+    }
+    if let Some(thread) = state.autosave_thread.take() {
+        if !thread.join().unwrap_or(false) {
+            state.dirty = true
+        }
+    }
+    if !state.autosave_due() {
+        return
+    }
+
+    state.dirty = false;
+    state.last_autosave = Instant::now();
+    // Serialized here since it needs the state, only writing it is slow
+    if let Some((generation, toml_string)) = serialize_config(state) {
+        state.autosave_thread = Some(std::thread::spawn(move || save_config(generation, &toml_string)));
+    }
+}
+
+fn config_from_state(state: &State) -> Map<String, Value> {
+    // Map is sorted by key, so the config keys are always saved in the same order
+    let mut config = Map::new();
+
+    let sort = if state.flags.sort_saved_players {
+        Sort {
+            column: SortColumn::Name,
+            ascending: true
+        }
+    } else {
+        Sort::new()
+    };
+    let saved = state.players.saved();
+    let player_list = state.players.sorted_indices(sort).into_iter().filter_map(|i| {
+        if saved[i] {
+            Some(state.players.player_list[i].to_toml())
+        } else {
+            None
+        }
+    }).collect();
+    config.insert(PLAYERS.to_string(), Value::Array(player_list));
+    config.insert(OPENED_WINDOW.to_string(), Value::Boolean(state.flags.display_window));
+    let mut profile_display_window: Map<_, _> = state.profile_display_window.iter()
+        .map(|(profile, open)| (profile.clone(), Value::Boolean(*open)))
+        .collect();
+    if !state.self_name.is_empty() {
+        profile_display_window.insert(state.self_name.clone(), Value::Boolean(state.flags.display_window));
+    }
+    config.insert(PROFILE_OPENED_WINDOW.to_string(), Value::Table(profile_display_window));
+    config.insert(INACTIVE_COLOR.to_string(), color_to_toml(state.inactive_color));
+    let comment_size = clamp_comment_size(state.comment_size).into_iter()
+        .map(|val| Value::Float(val as f64)).collect();
+    config.insert(COMMENT_SIZE.to_string(), Value::Array(comment_size));
+    config.insert(COMMENT_MAX_LEN.to_string(), Value::Integer(state.comment_max_len as i64));
+    config.insert(ROW_TINT_ALPHA.to_string(), Value::Float(state.row_tint_alpha as f64));
+    config.insert(COLUMN_SPLIT.to_string(), Value::Float(clamp_column_split(state.column_split) as f64));
+    let snippets = state.snippets.iter().map(|snippet| Value::String(snippet.clone())).collect();
+    config.insert(SNIPPETS.to_string(), Value::Array(snippets));
+    config.insert(AUTOSAVE_INTERVAL.to_string(), Value::Integer(state.autosave_interval as i64));
+    config.insert(PROFILE_URL.to_string(), Value::String(state.profile_url.clone()));
+    config.insert(MET_DATE_FORMAT.to_string(), Value::String(state.met_date_format.clone()));
+    if let Some(window_pos) = state.window_pos {
+        let window_pos = window_pos.into_iter().map(|val| Value::Float(val as f64)).collect();
+        config.insert(WINDOW_POS.to_string(), Value::Array(window_pos));
+    }
+    if let Some(window_size) = state.window_size {
+        let window_size = window_size.into_iter().map(|val| Value::Float(val as f64)).collect();
+        config.insert(WINDOW_SIZE.to_string(), Value::Array(window_size));
+    }
+    config.insert(SHOW_ALL.to_string(), Value::Boolean(state.flags.show_all));
+    config.insert(COMMENTED_ONLY.to_string(), Value::Boolean(state.flags.commented_only));
+    config.insert(SHOW_IGNORED.to_string(), Value::Boolean(state.flags.show_ignored));
+    config.insert(CASE_SENSITIVE.to_string(), Value::Boolean(state.filters.case_sensitive));
+    config.insert(SHOW_ENCOUNTERS.to_string(), Value::Boolean(state.flags.show_encounters));
+    config.insert(SHOW_RATING.to_string(), Value::Boolean(state.flags.show_rating));
+    config.insert(SHOW_PROFESSION.to_string(), Value::Boolean(state.flags.show_profession));
+    config.insert(SHOW_SUBGROUP.to_string(), Value::Boolean(state.flags.show_subgroup));
+    config.insert(SHOW_CHAT.to_string(), Value::Boolean(state.flags.show_chat));
+    config.insert(IMPORT_OVERWRITE.to_string(), Value::Boolean(state.flags.import_overwrite));
+    config.insert(KEEP_ALL.to_string(), Value::Boolean(state.players.keep_all));
+    config.insert(KEEP_ALL_LIMIT.to_string(), Value::Integer(state.players.keep_all_limit as i64));
+    config.insert(MAX_PLAYERS.to_string(), Value::Integer(state.players.max_players as i64));
+    config.insert(AUTO_ADD.to_string(), Value::Boolean(state.flags.auto_add));
+    config.insert(APPEND_COMMENT_ON_ADD.to_string(), Value::Boolean(state.flags.append_comment_on_add));
+    config.insert(FADE_ON_LEAVE.to_string(), Value::Boolean(state.flags.fade_on_leave));
+    config.insert(PRETTY_CONFIG.to_string(), Value::Boolean(state.flags.pretty_config));
+    config.insert(SORT_SAVED_PLAYERS.to_string(), Value::Boolean(state.flags.sort_saved_players));
+    config.insert(CONFIRM_BULK_ACTIONS.to_string(), Value::Boolean(state.flags.confirm_bulk_actions));
+    config.insert(CONFIRM_WIPE.to_string(), Value::Boolean(state.flags.confirm_wipe));
+    config.insert(FILE_QUERY.to_string(), Value::Boolean(state.flags.file_query));
+    config.insert(SELECT_ALL_ON_EDIT.to_string(), Value::Boolean(state.flags.select_all_on_edit));
+    config.insert(AUTO_SIZE_COMMENTS.to_string(), Value::Boolean(state.flags.auto_size_comments));
+    config.insert(COMMENT_MET_DATE.to_string(), Value::Boolean(state.flags.comment_met_date));
+    config.insert(SORT_BY_SCORE.to_string(), Value::Boolean(state.flags.sort_by_score));
+    config.insert(FILTER_MODE.to_string(), Value::String(state.filters.match_mode.config_name().to_string()));
+    config.insert(DEFAULT_SORT.to_string(), Value::String(state.default_sort.column.config_name().to_string()));
+    config.insert(DEFAULT_SORT_ASCENDING.to_string(), Value::Boolean(state.default_sort.ascending));
+    config.insert(SORT.to_string(), Value::String(state.sort.column.config_name().to_string()));
+    config.insert(SORT_ASCENDING.to_string(), Value::Boolean(state.sort.ascending));
+    config.insert(REMEMBER_SORT.to_string(), Value::Boolean(state.flags.remember_sort));
+    config.insert(SORT_ALPHABETICAL.to_string(), Value::Boolean(state.flags.sort_alphabetical));
+    config.insert(SQUAD_FIRST.to_string(), Value::Boolean(state.flags.squad_first));
+    config.insert(HIGHLIGHT_MATCHES.to_string(), Value::Boolean(state.flags.highlight_matches));
+    config.insert(HIDE_DISCRIMINATOR.to_string(), Value::Boolean(state.flags.hide_discriminator));
+    config.insert(COMPACT.to_string(), Value::Boolean(state.flags.compact));
+    let shortcuts = Shortcut::ALL.into_iter()
+        .filter_map(|shortcut| {
+            let key = state.shortcut_keys.get(&shortcut)?;
+            Some((shortcut.config_name().to_string(), Value::Integer(key.0 as i64)))
+        })
+        .collect();
+    config.insert(SHORTCUTS.to_string(), Value::Table(shortcuts));
+    config.insert(SHORTCUT_CTRL.to_string(), Value::Boolean(state.shortcut_modifiers.ctrl));
+    config.insert(SHORTCUT_ALT.to_string(), Value::Boolean(state.shortcut_modifiers.alt));
+    config.insert(SHORTCUT_SHIFT.to_string(), Value::Boolean(state.shortcut_modifiers.shift));
+    let filter_presets = state.filter_presets.iter().map(|preset| preset.to_toml()).collect();
+    config.insert(FILTER_PRESETS.to_string(), Value::Array(filter_presets));
+    let sessions = state.sessions.iter().map(SquadSession::to_toml).collect();
+    config.insert(SESSIONS.to_string(), Value::Array(sessions));
+
+    config
+}
+
+/// The config of `state`, and its generation for save_config(). Must be called with the state
+/// locked (as `&State` ensures), so newer states get higher generations
+fn serialize_config(state: &State) -> Option<(u64, String)> {
+    let config = Value::Table(config_from_state(state));
+    let toml_string = if state.flags.pretty_config {
+        toml::to_string_pretty(&config)
+    } else {
+        toml::to_string(&config)
+    };
+    match toml_string {
+        Ok(toml_string) => Some((NEXT_SAVE_GENERATION.fetch_add(1, Ordering::Relaxed), toml_string)),
+        Err(e) => {
+            log(LogLevel::Error, &format!("Couldn't serialize the config: {e}"));
+            None
+        }
+    }
+}
+
+/// Writes `toml_string` as the config, unless a newer generation has been written already.
+/// Returns false if it couldn't be written
+fn save_config(generation: u64, toml_string: &str) -> bool {
+    let mut saved_generation = SAVED_GENERATION.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if *saved_generation > generation {
+        return true
+    }
+    match write_config(toml_string) {
+        Ok(()) => {
+            *saved_generation = generation;
+            true
+        },
+        Err(e) => {
+            log(LogLevel::Error, &format!("Couldn't save {}: {e}", config_path()));
+            false
+        }
+    }
+}
+
+/// Writes to a temporary file and then replaces the config with it, so the
+/// config is never left half-written if the game is closed while saving
+fn write_config(toml_string: &str) -> std::io::Result<()> {
+    let (config_path, tmp_path) = (config_path(), config_sibling_path(TMP_EXTENSION));
+    create_parent_dir(&config_path)?;
+    // A failed backup shouldn't prevent saving
+    if !BACKUPS_ROTATED.swap(true, Ordering::SeqCst) {
+        if let Err(e) = rotate_backups() {
+            log(LogLevel::Warning, &format!("Couldn't back up {config_path}: {e}"))
+        }
+    }
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(toml_string.as_bytes())?;
+    file.sync_all()?;
+    std::mem::drop(file);
+
+    std::fs::rename(tmp_path, config_path)
+}
+
+/// Writes the name and comment of `players` to `path`. Returns how many were written
+fn export_csv<'a>(players: impl Iterator<Item = &'a Player>, path: &str) -> std::io::Result<usize> {
+    let mut csv = "name,comment\r\n".to_string();
+    let mut count = 0;
+    for player in players {
+        csv.push_str(&format!("{},{}\r\n", csv_field(&player.name), csv_field(&player.comment)));
+        count += 1;
+    }
+    create_parent_dir(path)?;
+    std::fs::write(path, csv)?;
+
+    Ok(count)
+}
+
+/// Merges the players in IMPORT_PATH into the list. Existing players get the imported comment
+/// appended, or replacing theirs if `overwrite`. New players check_new_player() refuses, and `self_name`,
+/// are refused. Returns how many rows were imported, skipped as malformed and refused
+fn import_csv(players: &mut PlayerVecMap, overwrite: bool, self_name: &str) -> std::io::Result<(usize, usize, usize)> {
+    let csv = std::fs::read_to_string(IMPORT_PATH)?;
+    let (mut imported, mut skipped, mut refused) = (0, 0, 0);
+    for (i, record) in parse_csv(&csv).into_iter().enumerate() {
+        let (name, comment) = match record.as_deref() {
+            Some([name, _]) if i == 0 && name == "name" => continue, // header
+            Some([name, comment]) if !name.trim().is_empty() => (name.trim(), comment),
+            Some([name]) if name.is_empty() => continue, // blank line
+            _ => {
+                skipped += 1;
+                continue
+            }
+        };
+        if name.eq_ignore_ascii_case(self_name) || (players.get(name).is_none() && players.check_new_player(name).is_err()) {
+            refused += 1;
+            continue
+        }
+        if !players.add_player(name, comment.clone(), true) && !comment.is_empty() {
+            if overwrite {
+                players.set_comment(name, comment)
+            } else if players.get(name).map(|player| &player.comment) != Some(comment) {
+                players.append_comment(name, comment)
+            }
+        }
+        imported += 1;
+    }
+
+    Ok((imported, skipped, refused))
+}
+
+/// Status shown after importing. `skipped` are described by `skipped_reason`, e.g. "malformed rows"
+fn import_status(imported: usize, skipped: usize, refused: usize, skipped_reason: &str) -> String {
+    let mut status = format!("Imported {imported} players");
+    if skipped != 0 {
+        status.push_str(&format!(", skipped {skipped} {skipped_reason}"));
+    }
+    if refused != 0 {
+        status.push_str(&format!(", refused {refused} (your own account, names that are too long, or the list is full)"));
+    }
+    status
+}
+
+/// The names in `text`, separated by new lines or commas, without surrounding whitespace nor blanks
+fn split_names(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c| c == '\n' || c == ',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/// Adds every name in `text` (see split_names()) with `comment`, except `self_name`. Unless `any_name`,
+/// names that don't look like account names are skipped, as are the ones check_new_player() refuses.
+/// Returns how many were added, already present and skipped, and why the last refused name was refused
+fn add_names(players: &mut PlayerVecMap, text: &str, comment: &str, any_name: bool, self_name: &str) -> (usize, usize, usize, Option<String>) {
+    let (mut added, mut present, mut skipped, mut refused) = (0, 0, 0, None);
+    for name in split_names(text) {
+        if name.eq_ignore_ascii_case(self_name) {
+            continue
+        }
+        if !any_name && !is_account_name(name) {
+            skipped += 1
+        } else if players.get(name).is_some() {
+            present += 1
+        } else if let Err(e) = players.check_new_player(name) {
+            skipped += 1;
+            refused = Some(e);
+        } else {
+            players.add_player(name, comment.to_string(), true);
+            added += 1
+        }
+    }
+
+    (added, present, skipped, refused)
+}
+
+/// Writes `players`, with everything Player::to_toml() saves, to `path`. Returns how many were written
+fn export_json<'a>(players: impl Iterator<Item = &'a Player>, path: &str) -> Result<usize, String> {
+    let player_list: Vec<_> = players.map(Player::to_toml).collect();
+    let count = player_list.len();
+    let json = serde_json::to_string_pretty(&Value::Array(player_list)).map_err(|e| e.to_string())?;
+    create_parent_dir(path).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| e.to_string())?;
+
+    Ok(count)
+}
+
+/// Merges the players in `path` (as written by export_json()) into the list. Existing players
+/// get the imported comment appended, or replacing theirs if `overwrite`, and the rest of the
+/// imported data merged in. New players check_new_player() refuses, and `self_name`, are refused.
+/// Returns how many players were imported, skipped as malformed and refused
+fn import_json(players: &mut PlayerVecMap, path: &str, overwrite: bool, self_name: &str) -> Result<(usize, usize, usize), String> {
+    let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let player_list = match serde_json::from_str::<Value>(&json).map_err(|e| e.to_string())? {
+        Value::Array(player_list) => player_list,
+        _ => return Err("Expected an array of players".to_string()),
+    };
+    let (mut imported, mut skipped, mut refused) = (0, 0, 0);
+    for val in player_list {
+        let mut player = match player_from_toml(val) {
+            Some(player) => player,
+            None => {
+                skipped += 1;
+                continue
+            }
+        };
+        if player.name.eq_ignore_ascii_case(self_name) || (players.get(&player.name).is_none() && players.check_new_player(&player.name).is_err()) {
+            refused += 1;
+            continue
+        }
+        match players.get_mut(&player.name) {
+            Some(existing) => {
+                if overwrite && !player.comment.is_empty() {
+                    existing.comment = player.comment.clone();
+                    existing.lowercase_comment = player.lowercase_comment.clone();
+                }
+                // Importing an export of the same list shouldn't count the encounters twice
+                existing.encounter_count = existing.encounter_count.max(player.encounter_count);
+                player.encounter_count = 0;
+                existing.merge(player);
+            },
+            None => {
+                // Like the CSV import, whatever the file says
+                player.added_manually = true;
+                players.insert(player)
+            },
+        }
+        imported += 1;
+    }
+
+    Ok((imported, skipped, refused))
+}
+
+/// Splits `csv` into records following RFC 4180. Malformed records
+/// (with an unterminated quote) are None
+fn parse_csv(csv: &str) -> Vec<Option<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (in_quotes, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"')
+            },
+            (true, '"') => in_quotes = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => in_quotes = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => (),
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(Some(std::mem::take(&mut record)));
+            },
+            (false, c) => field.push(c),
+        }
+    }
+    if in_quotes {
+        records.push(None)
+    } else if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(Some(record));
+    }
+
+    records
+}
+
+/// Quotes `field` if needed, following RFC 4180
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Creates the directory `path` is in, in case it doesn't exist (e.g. on unusual installs)
+fn create_parent_dir(path: &str) -> std::io::Result<()> {
+    match Path::new(path).parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(())
+    }
+}
+
+/// Each callback locks the state once and keeps the guard until it returns, passing `&mut State`
+/// to the functions it calls. The mutex isn't reentrant, so calling this while the state is
+/// already locked (e.g. from a function called by a callback) deadlocks the game
+fn get_state<'a>() -> MutexGuard<'a, State>{
+    // A panic while holding the lock shouldn't disable the addon for the rest of the session
+    STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn squad_update(users: UserInfoIter) {
+    let mut state = get_state();
+    for user in users {
+        if let Some(username) = user.account_name {
+            match user.role {
+                arcdps::extras::UserRole::None => remove_user(&mut state, username),
+                role => add_user(&mut state, username, Some(user.subgroup), matches!(role, arcdps::extras::UserRole::SquadLeader)),
+            }
+        }
+    }
+}
+
+fn remove_user(state: &mut State, username: &str) {
+    state.dirty = true;
+    let is_self = username == state.self_name;
+
+    if is_self {
+        state.self_in_squad = false;
+        state.new_in_squad.clear();
+        state.end_session();
+        state.request_wipe()
+    } else {
+        state.recently_left.retain(|name| name != username);
+        state.recently_left.push_front(username.to_string());
+        state.recently_left.truncate(RECENTLY_LEFT_CAPACITY);
+        state.players.user_left(username);
+    }
+}
+
+fn add_user(state: &mut State, username: &str, subgroup: Option<u8>, is_commander: bool) {
+    state.dirty = true;
+    let is_self = username == state.self_name;
+
+    // Joining a new squad before answering means the old one is done with
+    state.resolve_pending_wipe(true);
+    if is_self {
+        if !state.self_in_squad {
+            // A new squad, the players removed from the previous one are gone for good
+            state.players.last_deleted.clear();
+            state.new_in_squad.clear();
+            state.start_session();
+        }
+        state.self_in_squad = true;
+    } else {
+        let is_new = state.players.get(username).is_none();
+        if is_new {
+            state.new_in_squad.insert(username.to_lowercase());
+        }
+        state.add_to_session(username);
+        if is_new && !state.flags.auto_add {
+            return
+        }
+        state.players.join(username, &state.context);
+        if is_new && state.flags.comment_met_date {
+            let comment = format_date(unix_now(), &state.met_date_format);
+            state.players.set_comment(username, &comment);
+        }
+        if let Some(player) = state.players.get_mut(username) {
+            player.subgroup = subgroup;
+            player.is_commander = is_commander;
+            if let Some(profession) = state.professions.get(&username.to_lowercase()) {
+                player.profession = Some(profession.clone())
+            }
+        }
+    }
+}
+
+/// Marks the sender as having chatted just now, for the chat column
+fn chat_message(message: &ChatMessageInfo) {
+    let mut state = get_state();
+    if !state.flags.extras_initialized {
+        return
+    }
+    if let Some(player) = state.players.get_mut(message.account_name) {
+        player.last_chat = Some(Instant::now())
+    }
+}
+
+/// Only used for agent added and removed events, which arcdps sends for squad members. They tell
+/// their profession, and stand in for unofficial extras' squad updates when it isn't loaded
+fn combat(ev: Option<&CombatEvent>, src: Option<Agent>, dst: Option<Agent>, _skill_name: Option<&str>, _id: u64, _revision: u64) {
+    let src = match (ev, src) {
+        (None, Some(src)) if src.elite == 0 => src,
+        _ => return,
+    };
+
+    let mut state = get_state();
+    let state = state.deref_mut();
+    if src.prof == 0 {
+        // Agent removed: src.elite == 0 and src.prof == 0. Only src.id identifies it
+        if let Some(username) = state.tracked_agents.remove(&src.id) {
+            if !state.flags.extras_initialized {
+                // Agents are also removed on every map change, so this doesn't delete anyone. They are
+                // only marked as gone, like when leaving the squad with confirm_wipe enabled
+                if let Some(player) = state.players.get_mut(&username) {
+                    player.leave();
+                    state.dirty = true;
+                }
+            }
+        }
+        return
+    }
+
+    // Agent added: src.elite == 0 and src.prof != 0. The account name is in dst, prefixed with ':'
+    let dst = match dst {
+        Some(dst) => dst,
+        None => return,
+    };
+    let username = match dst.name {
+        Some(name) => name.trim_start_matches(':'),
+        None => return,
+    };
+    if let Some(profession) = profession_name(dst.prof, dst.elite) {
+        if let Some(player) = state.players.get_mut(username) {
+            if player.profession.as_deref() != Some(profession) {
+                player.profession = Some(profession.to_string());
+                state.dirty = true;
+            }
+        }
+        state.professions.insert(username.to_lowercase(), profession.to_string());
+    }
+
+    if dst.self_ != 0 {
+        if !state.flags.extras_initialized && state.self_name != username {
+            state.self_name = username.to_string();
+            state.apply_profile_display_window();
+            state.dirty = true;
+        }
+        return
+    }
+    // Keyed by the agent id, the only thing the removed event has
+    state.tracked_agents.insert(src.id, username.to_string());
+    if state.flags.extras_initialized {
+        // Extras keeps track of the squad, arcdps only confirms the player is still around
+        if let Some(player) = state.players.get_mut(username) {
+            player.last_seen = Some(unix_now());
+            state.dirty = true;
+        }
+    } else {
+        // dst.team is the subgroup starting at 1, or 0 if there are no subgroups
+        let subgroup = u8::try_from(dst.team).ok().and_then(|team| team.checked_sub(1));
+        add_user(state, username, subgroup, false);
+    }
+}
+
+/// Name of the elite specialization `elite`, or the profession `prof` if it's 0 (a core build)
+fn profession_name(prof: u32, elite: u32) -> Option<&'static str> {
+    let elite_name = match elite {
+        0 => None,
+        5 => Some("Druid"),
+        7 => Some("Daredevil"),
+        18 => Some("Berserker"),
+        27 => Some("Dragonhunter"),
+        34 => Some("Reaper"),
+        40 => Some("Chronomancer"),
+        43 => Some("Scrapper"),
+        48 => Some("Tempest"),
+        52 => Some("Herald"),
+        55 => Some("Soulbeast"),
+        56 => Some("Weaver"),
+        57 => Some("Holosmith"),
+        58 => Some("Deadeye"),
+        59 => Some("Mirage"),
+        60 => Some("Scourge"),
+        61 => Some("Spellbreaker"),
+        62 => Some("Firebrand"),
+        63 => Some("Renegade"),
+        64 => Some("Harbinger"),
+        65 => Some("Willbender"),
+        66 => Some("Virtuoso"),
+        67 => Some("Catalyst"),
+        68 => Some("Bladesworn"),
+        69 => Some("Vindicator"),
+        70 => Some("Mechanist"),
+        71 => Some("Specter"),
+        72 => Some("Untamed"),
+        // Unknown (e.g. newer) specializations fall back to the profession
+        _ => None,
+    };
+    if elite_name.is_some() {
+        return elite_name
+    }
+
+    match prof {
+        1 => Some("Guardian"),
+        2 => Some("Warrior"),
+        3 => Some("Engineer"),
+        4 => Some("Ranger"),
+        5 => Some("Thief"),
+        6 => Some("Elementalist"),
+        7 => Some("Mesmer"),
+        8 => Some("Necromancer"),
+        9 => Some("Revenant"),
+        _ => None,
+    }
+}
+
+fn draw_window(ui: &Ui, not_character_or_loading: bool) {
+    let mut state = get_state();
+    let state = state.deref_mut();
+    // Key up events are missed when the game loses focus (e.g. alt+tab), so resync every frame
+    let io = ui.io();
+    state.held_modifiers = Modifiers { ctrl: io.key_ctrl, alt: io.key_alt, shift: io.key_shift };
+    // nofilter has no access to imgui, so it relies on this
+    state.typing = io.want_text_input;
+    if !not_character_or_loading {
+        // Don't draw anything on character screen or loading screen
+        return
+    }
+
+    let mut opened_window = state.flags.display_window;
+    let (window_pos, window_size) = (state.window_pos, state.window_size);
+    if opened_window {
+        let mut window = arcdps::imgui::Window::new("Player List").opened(&mut opened_window).collapsible(false);
+        // Only restored once, after that the user is free to move the window
+        if let Some(window_pos) = window_pos {
+            window = window.position(window_pos, Condition::Once)
+        }
+        if let Some(window_size) = window_size {
+            window = window.size(window_size, Condition::Once)
+        }
+        window.build(ui, || {
+            state.window_pos = Some(ui.window_pos());
+            state.window_size = Some(ui.window_size());
+            if state.flags.compact {
+                ui.checkbox("Compact", &mut state.flags.compact);
+            }
+
+            if state.flags.compact && state.focus_add_user {
+                // Otherwise the focus would only be used up whenever the header is opened
+                ui.set_next_item_open(true);
+            }
+            if !state.flags.compact || ui.collapsing_header("Filters and adding", TreeNodeFlags::empty()) {
+                if !state.flags.extras_initialized {
+                    ui.text_disabled("Unofficial extras not found, using arcdps squad tracking");
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Commanders aren't shown, and leaving the squad doesn't remove anyone")
+                    }
+                }
+                ui.checkbox("Show all", &mut state.flags.show_all);
+                ui.same_line();
+                ui.checkbox("Only commented", &mut state.flags.commented_only);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Only show players with a comment.\nWith \"Show all\", everyone with a comment, in the squad or not")
+                }
+                ui.same_line();
+                ui.checkbox("Show ignored", &mut state.flags.show_ignored);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Players marked as ignored in their menu (right click their name)")
+                }
+                ui.same_line();
+                if !state.flags.compact {
+                    ui.checkbox("Compact", &mut state.flags.compact);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Only show the names, with the comments in their tooltips.\nEverything else is in a collapsed header")
+                    }
+                    ui.same_line();
+                }
+                let in_squad = state.players.player_list.iter().filter(|player| player.in_squad).count();
+                ui.text_disabled(format!("In squad: {in_squad}, new: {}", state.new_in_squad.len()));
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Players in the squad, and how many of the ones\nthat joined it weren't in the list before")
+                }
+                if !state.players.last_deleted.is_empty() {
+                    ui.same_line();
+                    if ui.button(format!("Undo delete ({})", state.players.last_deleted.len())) {
+                        state.players.undo_delete()
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Put back the players removed by the last delete")
+                    }
+                }
+
+                ui.text("Context:");
+                ui.same_line();
+                ui.input_text("##context", &mut state.context_text).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Players in the squad will be tagged with this context (e.g. raid, WvW)")
+                }
+                ui.same_line();
+                if ui.button("Set##context") {
+                    state.context = state.context_text.trim().to_string();
+                    state.players.add_context_to_squad(&state.context);
+                }
+
+                ui.separator();
+                ui.text("Add user:");
+                if state.focus_add_user {
+                    state.focus_add_user = false;
+                    ui.set_keyboard_focus_here();
+                }
+                if ui.input_text("##add_user", &mut state.add_user_text).build() {
+                    state.add_user_status = None
+                };
+                ui.same_line();
+                let valid_name = state.add_any_name || is_account_name(state.add_user_text.trim());
+                let disabled = ui.begin_disabled(!valid_name);
+                let add_clicked = ui.button("Add");
+                disabled.end();
+                ui.same_line();
+                ui.checkbox("Any name", &mut state.add_any_name);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Allow adding names that don't look like an account name (Name.1234)")
+                }
+                if !valid_name && !state.add_user_text.is_empty() {
+                    ui.text_colored(ERROR_COLOR, "Not an account name, e.g. Name.1234");
+                }
+                if add_clicked {
+                    // Names are validated trimmed, so they must be added trimmed too
+                    let username = state.add_user_text.trim().to_string();
+                    if state.is_self(&username) {
+                        state.add_user_status = Some(format!("{username} is you, you can't be added to the list"));
+                    } else if !username.is_empty() {
+                        let comment = state.new_player_comment();
+                        let checked = match state.players.get(&username) {
+                            Some(_) => Ok(()),
+                            None => state.players.check_new_player(&username),
+                        };
+                        if let Err(e) = checked {
+                            state.add_user_status = Some(e);
+                        } else if state.players.add_player(&username, comment, true) {
+                            state.add_user_status = None;
+                        } else {
+                            if state.flags.append_comment_on_add {
+                                state.players.append_comment(&username, DEFAULT_COMMENT);
+                            }
+                            state.add_user_status = Some(format!("{username} is already in the list"));
+                            // The stored name may be capitalized differently
+                            state.scroll_to = state.players.get(&username).map(|player| player.name.clone());
+                        }
+                    }
+                    state.add_user_text = "".to_string();
+                };
+                if let Some(status) = &state.add_user_status {
+                    ui.text(status);
+                }
+
+                ui.separator();
+                ui.text("Filters:");
+                for i in 0..state.filter_presets.len() {
+                    ui.same_line();
+                    if ui.button(format!("{}##preset_{i}", state.filter_presets[i].name)) {
+                        state.apply_filter_preset(i)
+                    }
+                }
+                ui.input_text("##quick_filter", &mut state.filters.quick_filter_str).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Filter by user name or comment")
+                }
+                ui.input_text("##user_filter", &mut state.filters.user_filter_str).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Filter by user name")
+                }
+                ui.input_text("##comment_filter", &mut state.filters.comment_filter_str).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Filter by comment")
+                }
+                ui.same_line();
+                ui.checkbox("Case sensitive", &mut state.filters.case_sensitive);
+                ui.same_line();
+                if ui.button("Clear##filters") {
+                    state.filters.clear()
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Clear the text and label filters.\nCan also be bound to a shortcut in the options")
+                }
+                let labels = state.players.all_labels();
+                if !labels.is_empty() {
+                    if let Some(_combo) = ui.begin_combo("##label_filter_combo", "Filter by label") {
+                        for label in labels {
+                            if Selectable::new(&label).build(ui) {
+                                state.filters.add_label_filter(label)
+                            }
+                        }
+                    }
+                }
+                if !state.filters.label_filters.is_empty() {
+                    ui.text("Labels:");
+                    let mut remove_label = None;
+                    for (i, label) in state.filters.label_filters.iter().enumerate() {
+                        ui.same_line();
+                        if ui.small_button(format!("{label} X##label_filter_{i}")) {
+                            remove_label = Some(i)
+                        }
+                    }
+                    if let Some(i) = remove_label {
+                        state.filters.label_filters.remove(i);
+                    }
+                    ui.same_line();
+                    ui.radio_button("Any##label_match", &mut state.filters.label_match_all, false);
+                    ui.same_line();
+                    ui.radio_button("All##label_match", &mut state.filters.label_match_all, true);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Only show players with all the selected labels")
+                    }
+                }
+
+                if state.flags.show_rating {
+                    ui.set_next_item_width(100.0);
+                    ui.slider("Min rating", 0, MAX_RATING, &mut state.filters.min_rating);
+                }
+
+                let filters = &state.filters;
+                let user_filter = filters.normalize(&filters.user_filter_str);
+                let comment_filter = filters.normalize(&filters.comment_filter_str);
+                let quick_filter = filters.normalize(&filters.quick_filter_str);
+                let shown = state.players.player_list.iter()
+                    .filter(|player| filters.shows(player, &user_filter, &comment_filter, &quick_filter, &state.flags))
+                    .count();
+                ui.text(format!("Showing {shown} / {}", state.players.player_list.len()));
+                if let Some(username) = &state.editing_comment {
+                    ui.same_line();
+                    ui.text_disabled("editing...");
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(format!("The comment of {username} is being edited.\nIt is kept when closing the window, and saved like every other change"))
+                    }
+                }
+
+                if let Some(username) = &state.focused_comment {
+                    for (i, snippet) in state.snippets.iter().enumerate() {
+                        if i != 0 {
+                            ui.same_line();
+                        }
+                        if ui.small_button(format!("{snippet}##snippet_{i}")) {
+                            state.players.append_comment(username, snippet)
+                        }
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text(format!("Add to the comment of {username}"))
+                        }
+                    }
+                }
+            }
+            // Actions that change the player list are applied after drawing the table,
+            // and must look players up by name since their indices may have changed
+            let mut actions = Vec::new();
+            let columns: Vec<_> = TableColumn::ALL.into_iter()
+                .filter(|column| column.is_visible(&state.flags) && (!state.flags.compact || *column == TableColumn::Name))
+                .collect();
+            // Tristate sorting allows going back to join order.
+            // The widths are saved in the config as column_split instead of in imgui's settings
+            let table_flags = TableFlags::SORTABLE | TableFlags::SORT_TRISTATE | TableFlags::RESIZABLE | TableFlags::NO_SAVED_SETTINGS;
+            if let Some(table) = ui.begin_table_with_flags("PLayerListTable", columns.len(), table_flags) {
+                for column in columns.iter() {
+                    // The other columns have a weight of 1, so the default split keeps every column the same width
+                    let (stretch, weight) = match column {
+                        TableColumn::Name => (TableColumnFlags::WIDTH_STRETCH, 2.0 * state.column_split),
+                        TableColumn::Comment => (TableColumnFlags::WIDTH_STRETCH, 2.0 * (1.0 - state.column_split)),
+                        _ => (TableColumnFlags::empty(), 0.0),
+                    };
+                    ui.table_setup_column_with(TableColumnSetup {
+                        name: column.header(),
+                        flags: state.sort.table_column_flags(column.sort_column()) | stretch,
+                        init_width_or_weight: weight,
+                        ..Default::default()
+                    });
+                }
+                ui.table_headers_row();
+                if let Some(sort_specs) = ui.table_sort_specs_mut() {
+                    sort_specs.conditional_sort(|specs| {
+                        state.sort = match specs.iter().next() {
+                            Some(spec) => Sort {
+                                column: columns.get(spec.column_idx())
+                                    .map(|column| column.sort_column())
+                                    .unwrap_or(SortColumn::Insertion),
+                                ascending: spec.sort_direction() != Some(TableSortDirection::Descending),
+                            },
+                            // A sort by a hidden column (e.g. from the config) has no header to show it,
+                            // so imgui reports no sort. It's kept, visible_indices sorts by any column
+                            None if !columns.iter().any(|column| column.sort_column() == state.sort.column) => state.sort,
+                            None => Sort::new(),
+                        }
+                    });
+                }
+                let shown = state.visible_indices();
+                let filters = &state.filters;
+                let players = &mut state.players;
+                // For highlighting the matches
+                let user_filter = filters.normalize(&filters.user_filter_str);
+                let quick_filter = filters.normalize(&filters.quick_filter_str);
+
+                // Keyboard navigation, only through the shown rows. Not while typing, since the keys are for the text
+                let mut scroll_to_selected = false;
+                if ui.is_window_focused_with_flags(WindowFocusedFlags::ROOT_AND_CHILD_WINDOWS) && !ui.io().want_text_input && !shown.is_empty() {
+                    let position = state.selected_player.as_deref()
+                        .and_then(|name| shown.iter().position(|i| players.player_list[*i].name == name));
+                    let new_position = if ui.is_key_pressed(Key::DownArrow) {
+                        Some(position.map_or(0, |position| (position + 1).min(shown.len() - 1)))
+                    } else if ui.is_key_pressed(Key::UpArrow) {
+                        Some(position.map_or(shown.len() - 1, |position| position.saturating_sub(1)))
+                    } else {
+                        None
+                    };
+                    if let Some(new_position) = new_position {
+                        state.selected_player = Some(players.player_list[shown[new_position]].name.clone());
+                        scroll_to_selected = true;
+                    }
+                    if let Some(position) = position {
+                        let name = &players.player_list[shown[position]].name;
+                        if ui.is_key_pressed(Key::Enter) {
+                            // Scrolling to the comment also starts editing it
+                            state.scroll_to = Some(name.clone())
+                        }
+                        if ui.is_key_pressed(Key::Delete) {
+                            // Confirmed even with confirm_bulk_actions off, since it's easy to press by accident
+                            state.pending_delete = Some((shown[position], name.clone()))
+                        }
+                    }
+                }
+
+                // Measured in the first row, to remember where the columns were dragged to
+                let (mut name_width, mut comment_width) = (None, None);
+                let mut editing_comment = None;
+                for (row, i) in shown.into_iter().enumerate() {
+                    // Duplicates from old configs share the name, so all but the one name_dict points at get the index too
+                    let is_duplicate = players.name_dict.get(&players.player_list[i].lowercase_name) != Some(&i);
+                    let player = &mut players.player_list[i];
+                    // The widgets of the row are keyed by name, not index, so they stay with their player
+                    // when the list changes, e.g. deleting or adding players while a comment is being edited
+                    let _row_id = ui.push_id(player.lowercase_name.as_str());
+                    let _duplicate_id = is_duplicate.then(|| ui.push_id(i as i32));
+                    ui.table_next_row();
+                    if let Some(tint) = row_tint(player, state.row_tint_alpha) {
+                        // ROW_BG0 so the selection, on ROW_BG1, is drawn on top
+                        ui.table_set_bg_color(TableBgTarget::ROW_BG0, tint);
+                    }
+                    if state.selected_player.as_deref() == Some(player.name.as_str()) {
+                        ui.table_set_bg_color(TableBgTarget::ROW_BG1, SELECTED_ROW_COLOR);
+                        if scroll_to_selected {
+                            ui.set_scroll_here_y();
+                        }
+                    }
+                    for column in columns.iter() {
+                        ui.table_next_column();
+                        match column {
+                            TableColumn::Name => {
+                                if row == 0 {
+                                    name_width = Some(ui.content_region_avail()[0])
+                                }
+                                if !state.flags.compact {
+                                    if ui.button("X##delete") {
+                                        actions.push(Action::DeletePlayer(i, player.name.clone()))
+                                    }
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Delete this player\nfrom the list")
+                                    }
+                                    ui.same_line();
+                                }
+                                let text_color = ui.style_color(StyleColor::Text);
+                                if player.pinned {
+                                    ui.text_disabled("^");
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Pinned")
+                                    }
+                                    ui.same_line();
+                                }
+                                if player.is_commander {
+                                    // Its own color, so it stands out whatever the name's color is
+                                    ui.text_colored(COMMANDER_COLOR, "[C]");
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Commander")
+                                    }
+                                    ui.same_line();
+                                }
+                                if player.added_manually {
+                                    ui.text_disabled("+");
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Added by hand")
+                                    }
+                                    ui.same_line();
+                                }
+                                let color = name_color(player, text_color, state.inactive_color, state.flags.fade_on_leave);
+                                let highlight = match state.flags.highlight_matches {
+                                    true => filters.match_range(&player.name, &player.lowercase_name, &user_filter)
+                                        .or_else(|| filters.match_range(&player.name, &player.lowercase_name, &quick_filter)),
+                                    false => None,
+                                };
+                                let name = match state.flags.hide_discriminator {
+                                    true => strip_discriminator(&player.name),
+                                    false => &player.name,
+                                };
+                                // The highlight may be in the hidden part
+                                let highlight = highlight
+                                    .filter(|(start, _)| *start < name.len())
+                                    .map(|(start, end)| (start, end.min(name.len())));
+                                draw_name(ui, name, color.unwrap_or(text_color), highlight);
+                                if ui.is_item_hovered() {
+                                    match state.flags.compact && !player.comment.is_empty() {
+                                        true => ui.tooltip_text(format!("{}\n\n{}", player.comment, player.details())),
+                                        false => ui.tooltip_text(player.details()),
+                                    }
+                                }
+                                if ui.is_item_clicked() {
+                                    state.selected_player = Some(player.name.clone())
+                                }
+                                let menu_id = "player_menu";
+                                if ui.is_item_clicked_with_button(MouseButton::Right) {
+                                    state.rename_text = player.name.clone();
+                                    state.note_text.clear();
+                                    state.rename_status = None;
+                                    ui.open_popup(menu_id)
+                                }
+                                // There is no comment column in compact mode, so the comment is edited in the menu
+                                let edit_in_menu = state.flags.compact && state.scroll_to.as_deref() == Some(player.name.as_str());
+                                if edit_in_menu {
+                                    ui.set_scroll_here_y();
+                                    state.rename_text = player.name.clone();
+                                    state.note_text.clear();
+                                    state.rename_status = None;
+                                    ui.open_popup(menu_id)
+                                }
+                                ui.popup(menu_id, || {
+                                    if state.flags.compact {
+                                        if edit_in_menu {
+                                            ui.set_keyboard_focus_here();
+                                        }
+                                        if ui.input_text_multiline("##menu_comment", &mut player.comment, state.comment_size)
+                                            .callback(InputTextMultilineCallback::EDIT, CommentLimit(state.comment_max_len))
+                                            .build() {
+                                            player.sync_lowercase_comment()
+                                        };
+                                        if ui.is_item_deactivated() {
+                                            player.sync_lowercase_comment()
+                                        }
+                                        ui.separator();
+                                    }
+                                    ui.checkbox("Pinned", &mut player.pinned);
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Pin to the top of the list.\nPinned players are shown\neven when out of the squad")
+                                    }
+                                    ui.same_line();
+                                    ui.checkbox("Ignored", &mut player.ignored);
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Someone to avoid. Dimmed and moved to the bottom of the list,\nor hidden if \"Show ignored\" is unchecked")
+                                    }
+                                    let mut color = player.color.unwrap_or(text_color);
+                                    if ColorEdit::new("Name color", &mut color).build(ui) {
+                                        player.color = Some(color)
+                                    }
+                                    if player.color.is_some() {
+                                        ui.same_line();
+                                        if ui.button("Default") {
+                                            player.color = None;
+                                        }
+                                    }
+                                    ui.separator();
+                                    if ui.button("Copy") {
+                                        // Copied as stored, including the discriminator
+                                        ui.set_clipboard_text(&player.name);
+                                        ui.close_current_popup();
+                                    }
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Copy account name")
+                                    }
+                                    ui.same_line();
+                                    if ui.button("Open profile") {
+                                        if let Err(e) = open_url(&profile_url(&state.profile_url, &player.name)) {
+                                            log(LogLevel::Warning, &e);
+                                        }
+                                        ui.close_current_popup();
+                                    }
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text(format!("Open {} in the browser.\nThe URL can be changed in the options", profile_url(&state.profile_url, &player.name)))
+                                    }
+                                    ui.input_text("##rename", &mut state.rename_text).build();
+                                    ui.same_line();
+                                    if ui.button("Rename") {
+                                        actions.push(Action::RenamePlayer(i, player.name.clone(), state.rename_text.trim().to_string()))
+                                    }
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Fix a mistyped account name, keeping the comment and labels")
+                                    }
+                                    if let Some(status) = &state.rename_status {
+                                        ui.text(status);
+                                    }
+                                    ui.input_text("##dated_note", &mut state.note_text).build();
+                                    ui.same_line();
+                                    if ui.button("Add dated note") {
+                                        player.add_dated_note(&state.note_text, unix_now());
+                                        state.note_text.clear();
+                                    }
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Add a line with today's date (UTC) and the text to the end of the comment")
+                                    }
+                                    ui.separator();
+                                    ui.text("Labels:");
+                                    let mut remove_label = None;
+                                    for (j, label) in player.labels.iter().enumerate() {
+                                        ui.text(label);
+                                        ui.same_line();
+                                        if ui.small_button(format!("X##remove_label_{j}")) {
+                                            remove_label = Some(j)
+                                        }
+                                    }
+                                    if let Some(j) = remove_label {
+                                        player.labels.remove(j);
+                                    }
+                                    ui.input_text("##new_label", &mut state.label_text).build();
+                                    ui.same_line();
+                                    if ui.button("Add label") {
+                                        player.add_label(&state.label_text);
+                                        state.label_text = "".to_string();
+                                    }
+                                });
+                                // In compact mode they are in the tooltip
+                                let labels = if state.flags.compact { &[][..] } else { &player.labels[..] };
+                                for label in labels {
+                                    ui.same_line();
+                                    if ui.small_button(format!("{label}##label")) {
+                                        actions.push(Action::FilterLabel(label.clone()))
+                                    }
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text("Filter by this label\nRight click the name to edit labels")
+                                    }
+                                }
+                            },
+                            TableColumn::Comment => {
+                                if row == 0 {
+                                    comment_width = Some(ui.content_region_avail()[0])
+                                }
+                                if state.scroll_to.as_deref() == Some(player.name.as_str()) {
+                                    ui.set_scroll_here_y();
+                                    ui.set_keyboard_focus_here();
+                                }
+                                let comment_size = match state.flags.auto_size_comments {
+                                    true => auto_comment_size(ui, &player.comment, state.comment_size),
+                                    false => state.comment_size,
+                                };
+                                if ui.input_text_multiline("##comment", &mut player.comment, comment_size)
+                                    .auto_select_all(state.flags.select_all_on_edit)
+                                    .callback(InputTextMultilineCallback::EDIT, CommentLimit(state.comment_max_len))
+                                    .build() {
+                                    player.sync_lowercase_comment()
+                                };
+                                // build() only tells about changes made this frame, so anything edited
+                                // in the frame focus was lost in would be missed by the filters
+                                if ui.is_item_deactivated() {
+                                    player.sync_lowercase_comment()
+                                }
+                                if ui.is_item_activated() {
+                                    state.focused_comment = Some(player.name.clone())
+                                }
+                                if ui.is_item_active() {
+                                    editing_comment = Some(player.name.clone())
+                                }
+                                if state.comment_max_len != 0 && ui.is_item_active() {
+                                    ui.text(format!("{}/{}", player.comment.chars().count(), state.comment_max_len));
+                                }
+                            },
+                            TableColumn::LastSeen => {
+                                if let (false, Some(last_seen)) = (player.in_squad, player.last_seen) {
+                                    ui.text(format_time_ago(last_seen));
+                                }
+                            },
+                            TableColumn::Encounters => ui.text(player.encounter_count.to_string()),
+                            TableColumn::Rating => {
+                                for star in 1..=MAX_RATING {
+                                    if star != 1 {
+                                        ui.same_line_with_spacing(0.0, 2.0);
+                                    }
+                                    if star <= player.rating {
+                                        ui.text_colored(STAR_COLOR, "*");
+                                    } else {
+                                        ui.text_disabled("*");
+                                    }
+                                    if ui.is_item_clicked() {
+                                        // Clicking the current rating removes it
+                                        player.rating = if player.rating == star { 0 } else { star };
+                                        // Text is never active, so is_any_item_active() below misses it
+                                        state.dirty = true;
+                                    }
+                                }
+                            },
+                            TableColumn::Profession => if let Some(profession) = &player.profession {
+                                ui.text(profession)
+                            },
+                            // Shown as in game, starting at 1
+                            TableColumn::Subgroup => if let (true, Some(subgroup)) = (player.in_squad, player.subgroup) {
+                                ui.text((subgroup as u32 + 1).to_string())
+                            },
+                            TableColumn::Chat => if let Some(last_chat) = player.last_chat {
+                                let elapsed = last_chat.elapsed();
+                                if elapsed < CHAT_INDICATOR_DURATION {
+                                    let mut color = CHAT_COLOR;
+                                    color[3] = 1.0 - elapsed.as_secs_f32() / CHAT_INDICATOR_DURATION.as_secs_f32();
+                                    ui.text_colored(color, "...");
+                                    if ui.is_item_hovered() {
+                                        ui.tooltip_text(format!("Wrote in the squad chat {}s ago", elapsed.as_secs()))
+                                    }
+                                }
+                            },
+                        }
+                    }
+                }
+                if let (Some(name_width), Some(comment_width)) = (name_width, comment_width) {
+                    if name_width + comment_width > 0.0 {
+                        state.column_split = clamp_column_split(name_width / (name_width + comment_width))
+                    }
+                }
+                state.editing_comment = editing_comment;
+                state.scroll_to = None;
+                table.end()
+            };
+
+            if !actions.is_empty() || ui.is_any_item_active() {
+                state.dirty = true
+            }
+            for action in actions {
+                match action {
+                    Action::DeletePlayer(index, username) => state.players.delete_row(index, &username),
+                    Action::FilterLabel(label) => state.filters.add_label_filter(label),
+                    Action::RenamePlayer(index, username, new_name) => {
+                        state.rename_status = match state.rename_player(index, &username, &new_name) {
+                            Ok(()) => None,
+                            Err(e) => Some(e),
+                        }
+                    },
+                }
+            }
+
+            if !state.flags.compact && !state.sessions.is_empty() && ui.collapsing_header("Past squads", TreeNodeFlags::empty()) {
+                ui.input_text("##session_filter", &mut state.session_filter).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Only show the squads a player was in")
+                }
+                let session_filter = state.session_filter.to_lowercase();
+                for (i, session) in state.sessions.iter().enumerate().rev() {
+                    if !session_filter.is_empty() && !session.players.iter().any(|name| name.to_lowercase().contains(&session_filter)) {
+                        continue
+                    }
+                    let duration = match session.ended_at {
+                        Some(ended_at) => format!(" for {}", format_duration(Duration::from_secs((ended_at - session.started_at).max(0) as u64))),
+                        None => String::new(),
+                    };
+                    let context = match session.context.as_str() {
+                        "" => String::new(),
+                        context => format!(" ({context})"),
+                    };
+                    if let Some(_node) = ui.tree_node(format!("{}{duration}{context}, {} players##session_{i}", format_time_ago(session.started_at), session.players.len())) {
+                        for name in session.players.iter() {
+                            ui.text(name);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // Only the close button can have changed it, the state has been locked the whole time
+    state.flags.display_window = opened_window;
+    if !opened_window {
+        state.finish_comment_edit();
+    }
+    state.resolve_pending_wipe(false);
+    if let Some(action) = state.pending_bulk_action {
+        bulk_action_confirmation(ui, state, action);
+    }
+    if let Some((index, username)) = state.pending_delete.clone() {
+        delete_confirmation(ui, state, index, &username);
+    }
+    poll_file_query(state);
+    autosave(state);
+}
+
+/// Lets external tools read the list from ROSTER_PATH (rewritten when it changes), and look players up by writing
+/// account names (one per line) to QUERY_PATH. The comments are written to QUERY_RESPONSE_PATH
+fn poll_file_query(state: &mut State) {
+    if !state.flags.file_query {
+        return
+    }
+    if let Some(last_file_query) = state.last_file_query {
+        if last_file_query.elapsed() < FILE_QUERY_INTERVAL {
+            return
+        }
+    }
+    state.last_file_query = Some(Instant::now());
+
+    let players: Vec<_> = state.players.player_list.iter().map(|player| serde_json::json!({
+        "name": player.name,
+        "comment": player.comment,
+        "in_squad": player.in_squad,
+    })).collect();
+    let roster = serde_json::json!({
+        "version": ROSTER_VERSION,
+        "players": players,
+    }).to_string();
+    if roster != state.last_roster {
+        let written = std::fs::write(ROSTER_TMP_PATH, &roster)
+            .and_then(|_| std::fs::rename(ROSTER_TMP_PATH, ROSTER_PATH));
+        match written {
+            Ok(()) => state.last_roster = roster,
+            Err(e) => log(LogLevel::Warning, &format!("Couldn't write {ROSTER_PATH}: {e}")),
+        }
+    }
+
+    let query = match std::fs::read_to_string(QUERY_PATH) {
+        Ok(query) => query,
+        Err(e) if e.kind() == ErrorKind::NotFound => return,
+        Err(e) => {
+            // Malformed (e.g. not UTF-8) queries are discarded
+            log(LogLevel::Warning, &format!("Couldn't read {QUERY_PATH}: {e}"));
+            let _ = std::fs::remove_file(QUERY_PATH);
+            return
+        }
+    };
+    let _ = std::fs::remove_file(QUERY_PATH);
+
+    let mut response = serde_json::Map::new();
+    for username in query.lines().map(str::trim).filter(|username| !username.is_empty()) {
+        let comment = match state.players.get(username) {
+            Some(player) => serde_json::Value::String(player.comment.clone()),
+            None => serde_json::Value::Null,
+        };
+        response.insert(username.to_string(), comment);
+    }
+    if let Err(e) = std::fs::write(QUERY_RESPONSE_PATH, serde_json::Value::Object(response).to_string()) {
+        log(LogLevel::Warning, &format!("Couldn't write {QUERY_RESPONSE_PATH}: {e}"));
+    }
+}
+
+fn delete_confirmation(ui: &Ui, state: &mut State, index: usize, username: &str) {
+    arcdps::imgui::Window::new("Confirm delete##player_list").collapsible(false).always_auto_resize(true).build(ui, || {
+        ui.text(format!("Delete {username} from the list?"));
+        if ui.button("Delete") {
+            state.pending_delete = None;
+            state.players.delete_row(index, username);
+            state.dirty = true;
+        }
+        ui.same_line();
+        if ui.button("Cancel") {
+            state.pending_delete = None;
+        }
+    });
+}
+
+fn bulk_action_confirmation(ui: &Ui, state: &mut State, action: BulkAction) {
+    arcdps::imgui::Window::new("Confirm##player_list").collapsible(false).always_auto_resize(true).build(ui, || {
+        ui.text(action.description(action.affected_players(state)));
+        let (confirm_label, cancel_label) = action.button_labels();
+        if ui.button(confirm_label) {
+            state.pending_bulk_action = None;
+            if let BulkAction::RemoveUncommented = action {
+                state.wipe_requested_at = None;
+            }
+            state.perform_bulk_action(action);
+        }
+        ui.same_line();
+        if ui.button(cancel_label) {
+            state.pending_bulk_action = None;
+            if let BulkAction::RemoveUncommented = action {
+                state.wipe_requested_at = None;
+            }
+        }
+    });
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// How long ago the unix timestamp `timestamp` was, e.g. "3d ago"
+fn format_time_ago(timestamp: i64) -> String {
+    let secs = (unix_now() - timestamp).max(0);
+    if secs < 60 {
+        "just now".to_string()
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86400)
+    }
+}
+
+/// `format` with {year}, {month} and {day} replaced by the UTC date of `timestamp`, in unix seconds
+fn format_date(timestamp: i64, format: &str) -> String {
+    // Days since 1970-01-01 to civil date, from Howard Hinnant's date algorithms
+    let days = timestamp.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format.replace("{year}", &format!("{year:04}"))
+        .replace("{month}", &format!("{month:02}"))
+        .replace("{day}", &format!("{day:02}"))
+}
+
+/// Human readable duration, e.g. "1h 5m"
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes) = (secs / 3600, (secs % 3600) / 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// Background of the row of `player`, if it's pinned or has a color
+fn row_tint(player: &Player, alpha: f32) -> Option<[f32;4]> {
+    if alpha <= 0.0 {
+        return None
+    }
+    let [r, g, b, _] = match (player.color, player.pinned) {
+        (Some(color), _) => color,
+        (None, true) => PINNED_ROW_COLOR,
+        (None, false) => return None,
+    };
+    Some([r, g, b, alpha])
+}
+
+/// None means the default text color
+fn name_color(player: &Player, text_color: [f32;4], inactive_color: [f32;4], fade_on_leave: bool) -> Option<[f32;4]> {
+    if player.ignored {
+        return Some(IGNORED_COLOR)
+    }
+    if player.color.is_some() {
+        return player.color
+    }
+    if player.in_squad {
+        return None
+    }
+
+    match player.left_at {
+        Some(left_at) if fade_on_leave && left_at.elapsed() < FADE_DURATION => {
+            let progress = left_at.elapsed().as_secs_f32() / FADE_DURATION.as_secs_f32();
+            Some(interpolate_color(text_color, inactive_color, progress))
+        },
+        _ => Some(inactive_color),
+    }
+}
+
+/// How well `query` matches `text`, if all of its characters are in `text` in the same order.
+/// Characters right after the previous match and at the start of words score more
+fn fuzzy_score(text: &str, query: &str) -> Option<u32> {
+    // Matching greedily can miss a better alignment, e.g. "oe" in "john doe" takes the "o" of "john",
+    // so `query` as a whole is tried too
+    let contiguous = text.find(query)
+        .and_then(|start| greedy_fuzzy_score(&text[start..], query, text[..start].chars().last()));
+    greedy_fuzzy_score(text, query, None).max(contiguous)
+}
+
+/// fuzzy_score() matching each character of `query` with the first one that fits.
+/// `previous` is the character before `text`, if any
+fn greedy_fuzzy_score(text: &str, query: &str, previous: Option<char>) -> Option<u32> {
+    let mut query_chars = query.chars().peekable();
+    let mut score = 0;
+    let (mut previous, mut previous_matched) = (previous, false);
+    for c in text.chars() {
+        let next = match query_chars.peek() {
+            Some(next) => *next,
+            None => break,
+        };
+        let matched = c == next;
+        if matched {
+            query_chars.next();
+            score += 1;
+            if previous_matched {
+                score += 2
+            }
+            if previous.map_or(true, |previous: char| !previous.is_alphanumeric()) {
+                score += 2
+            }
+        }
+        previous = Some(c);
+        previous_matched = matched;
+    }
+
+    query_chars.peek().is_none().then_some(score)
+}
+
+/// Below this, fuzzy_score() matches are too scattered to be shown. Twice the length of `query`,
+/// e.g. half of it together or at the start of words
+fn fuzzy_min_score(query: &str) -> u32 {
+    2 * query.chars().count() as u32
+}
+
+/// `template` with {name} replaced by `name`, percent-encoded
+fn profile_url(template: &str, name: &str) -> String {
+    let mut encoded = String::new();
+    for b in name.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    template.replace("{name}", &encoded)
+}
+
+/// Opens `url` in the default browser. Only web URLs are opened, since anything else
+/// (e.g. a path to a program, from a hand-edited config) would be run
+fn open_url(url: &str) -> Result<(), String> {
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(format!("Not opening {url}, it isn't a web URL"))
+    }
+    std::process::Command::new("rundll32")
+        .args(["url.dll,FileProtocolHandler", url])
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Couldn't open {url}: {e}"))
+}
+
+/// Names can't be longer than MAX_NAME_LEN characters
+fn check_name_len(name: &str) -> Result<(), String> {
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(format!("Names can't be longer than {MAX_NAME_LEN} characters"))
+    }
+
+    Ok(())
+}
+
+/// Whether `name` looks like an account name: word characters or spaces, a dot and 4 digits (e.g. "John Doe.1234")
+fn is_account_name(name: &str) -> bool {
+    match name.rsplit_once('.') {
+        Some((base, discriminator)) => !base.trim().is_empty()
+            && base.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ' ')
+            && discriminator.len() == 4
+            && discriminator.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// `name` without the trailing ".1234" of account names, if it has one
+fn strip_discriminator(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((base, discriminator)) if !base.is_empty()
+            && discriminator.len() == 4
+            && discriminator.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => name,
+    }
+}
+
+/// Size of a comment box just tall enough for the lines of `comment`, but not bigger than `max_size`.
+/// Comment boxes don't wrap, so only explicit line breaks add lines
+fn auto_comment_size(ui: &Ui, comment: &str, max_size: [f32;2]) -> [f32;2] {
+    let lines = comment.lines().count().max(1) + comment.ends_with('\n') as usize;
+    let padding = ui.clone_style().frame_padding[1];
+    let height = lines as f32 * ui.text_line_height() + 2.0 * padding;
+    [max_size[0], height.min(max_size[1])]
+}
+
+/// Draws `name`, with the byte range `highlight` in HIGHLIGHT_COLOR. It's a single item,
+/// so it can be hovered or clicked as a whole
+fn draw_name(ui: &Ui, name: &str, color: [f32;4], highlight: Option<(usize, usize)>) {
+    let (start, end) = match highlight {
+        Some(range) => range,
+        None => return ui.text_colored(color, name),
+    };
+    ui.group(|| {
+        let segments = [(&name[..start], color), (&name[start..end], HIGHLIGHT_COLOR), (&name[end..], color)];
+        let mut first = true;
+        for (segment, color) in segments.into_iter().filter(|(segment, _)| !segment.is_empty()) {
+            if !first {
+                ui.same_line_with_spacing(0.0, 0.0)
+            }
+            ui.text_colored(color, segment);
+            first = false;
+        }
+    });
+}
+
+fn interpolate_color(from: [f32;4], to: [f32;4], progress: f32) -> [f32;4] {
+    let mut color = from;
+    for (component, to) in color.iter_mut().zip(to) {
+        *component += (to - *component) * progress
+    }
+
+    color
+}
+
+/// Cuts comments being typed down to this many characters. 0 means no limit
+struct CommentLimit(u32);
+
+impl InputTextCallbackHandler for CommentLimit {
+    fn on_edit(&mut self, mut data: TextCallbackData) {
+        if self.0 == 0 {
+            return
+        }
+        // Counted in characters, so multibyte characters aren't split
+        if let Some((byte_index, _)) = data.str().char_indices().nth(self.0 as usize) {
+            let excess = data.str()[byte_index..].chars().count();
+            data.remove_chars(byte_index, excess);
+        }
+    }
+}
+
+enum Action {
+    /// Index and name, since the name alone can't tell apart duplicates from old configs
+    DeletePlayer(usize, String),
+    FilterLabel(String),
+    /// Index, old name and new name. The index like DeletePlayer
+    RenamePlayer(usize, String, String),
+}
+
+/// Adds a checkbox for the window to the arcdps windows menu, next to "error"
+fn options(ui: &Ui, window_name: Option<&str>) -> bool {
+    if let Some("error") = window_name {
+        // Works even before extras is initialized, the window just won't be drawn until then
+        ui.checkbox("player list", &mut get_state().flags.display_window);
+    }
+
+    false
+}
+
+fn options_tab(ui: &Ui) {
+    let mut state = get_state();
+    let state = state.deref_mut();
+    ColorEdit::new("Inactive player", &mut state.inactive_color).build(ui);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Color of the names of players out of the squad")
+    }
+
+    if ui.input_float2("Comment Size", &mut state.comment_size).build() {
+        state.comment_size = clamp_comment_size(state.comment_size)
+    }
+    ui.checkbox("Fit comment height to its lines", &mut state.flags.auto_size_comments);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Comment Size height becomes the maximum height")
+    }
+    let mut comment_max_len = state.comment_max_len.min(i32::MAX as u32) as i32;
+    if ui.input_int("Max comment length", &mut comment_max_len).build() {
+        state.comment_max_len = comment_max_len.max(0) as u32
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("In characters. 0 means no limit.\nOnly applies while typing, longer comments are kept")
+    }
+    ui.slider("Row tint", 0.0, 1.0, &mut state.row_tint_alpha);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Opacity of the row background of pinned players and players with a color. 0 disables it")
+    }
+    let mut autosave_interval = state.autosave_interval.min(i32::MAX as u32) as i32;
+    if ui.input_int("Autosave interval", &mut autosave_interval).build() {
+        state.autosave_interval = autosave_interval.max(0) as u32
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("In seconds, only when something changed. 0 means only saving when the game is closed")
+    }
+    ui.input_text("Profile URL", &mut state.profile_url).build();
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Opened by \"Open profile\" in the menu of a player (right click their name).\n{name} is replaced by the account name")
+    }
+    ui.same_line();
+    if ui.small_button("Default##profile_url") {
+        state.profile_url = DEFAULT_PROFILE_URL.to_string()
+    }
+    ui.checkbox("Comment the date new players were met", &mut state.flags.comment_met_date);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Instead of \"Comment here\" or an empty comment.\nSince players with a comment are saved, everyone you meet is kept, like with \"Keep all players\"")
+    }
+    ui.input_text("Met date format", &mut state.met_date_format).build();
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("{{year}}, {{month}} and {{day}} are replaced by the date (UTC), e.g. \"{}\"", format_date(unix_now(), &state.met_date_format)))
+    }
+    ui.same_line();
+    if ui.small_button("Default##met_date_format") {
+        state.met_date_format = DEFAULT_MET_DATE_FORMAT.to_string()
+    }
+    ui.text("Comment snippets");
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Buttons above the table that add their text to the comment edited last")
+    }
+    let mut removed_snippet = None;
+    for (i, snippet) in state.snippets.iter().enumerate() {
+        if ui.small_button(format!("X##remove_snippet_{i}")) {
+            removed_snippet = Some(i)
+        }
+        ui.same_line();
+        ui.text(snippet);
+    }
+    if let Some(i) = removed_snippet {
+        state.snippets.remove(i);
+    }
+    ui.input_text("##snippet", &mut state.snippet_text).build();
+    ui.same_line();
+    if ui.button("Add snippet") {
+        let snippet = state.snippet_text.trim();
+        if !snippet.is_empty() && !state.snippets.iter().any(|s| s == snippet) {
+            state.snippets.push(snippet.to_string());
+        }
+        state.snippet_text.clear();
+    }
+    ui.checkbox("Show encounters column", &mut state.flags.show_encounters);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("How many times each player has joined your squad")
+    }
+    ui.checkbox("Show profession column", &mut state.flags.show_profession);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Elite specialization of each player, as of the last time arcdps saw them")
+    }
+    ui.checkbox("Show subgroup column", &mut state.flags.show_subgroup);
+    ui.checkbox("Show rating column", &mut state.flags.show_rating);
+    ui.checkbox("Show chat column", &mut state.flags.show_chat);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Marks who wrote in the squad chat in the last few seconds.\nNeeds unofficial extras")
+    }
+
+    ui.checkbox("Append comment when adding an existing player", &mut state.flags.append_comment_on_add);
+    ui.checkbox("Select whole comment when editing", &mut state.flags.select_all_on_edit);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("When clicking a comment, select all of it so typing replaces it instead of appending to it")
+    }
+    ui.checkbox("Fade out players that leave", &mut state.flags.fade_on_leave);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Gradually change the color of players that leave the squad to the inactive color")
+    }
+    ui.checkbox("Highlight filter matches", &mut state.flags.highlight_matches);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Color the part of each name that matches the name filter")
+    }
+    ui.checkbox("Hide account number", &mut state.flags.hide_discriminator);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Show \"Name\" instead of \"Name.1234\" in the list.\nThe full name is still saved, filtered and copied")
+    }
+
+    ui.checkbox("Pretty config file", &mut state.flags.pretty_config);
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Save {} in a more readable format", config_path()))
+    }
+    ui.same_line();
+    ui.checkbox("Sort saved players", &mut state.flags.sort_saved_players);
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Save the players in {} sorted by name, so the file changes less between saves.\nThe join order will be lost", config_path()))
+    }
+
+    ui.text("Filters match:");
+    ui.same_line();
+    ui.radio_button("Anywhere", &mut state.filters.match_mode, MatchMode::Contains);
+    ui.same_line();
+    ui.radio_button("Start only", &mut state.filters.match_mode, MatchMode::StartsWith);
+    ui.same_line();
+    ui.radio_button("Fuzzy", &mut state.filters.match_mode, MatchMode::Fuzzy);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("The letters of the filter must appear in order, but not necessarily together.\ne.g. \"jdoe\" matches \"John Doe.1234\"")
+    }
+    if state.filters.match_mode == MatchMode::Fuzzy {
+        ui.same_line();
+        ui.checkbox("Best matches first", &mut state.flags.sort_by_score);
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Sort by how well the names match the filters, unless the table is sorted by a column")
+        }
+    }
+
+    ui.text("Default sort:");
+    for column in SortColumn::ALL {
+        ui.same_line();
+        ui.radio_button(column.name(), &mut state.default_sort.column, column);
+    }
+    ui.same_line();
+    let mut descending = !state.default_sort.ascending;
+    if ui.checkbox("Descending", &mut descending) {
+        state.default_sort.ascending = !descending
+    }
+    ui.checkbox("Remember last sort", &mut state.flags.remember_sort);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Start with the sort used last time (set by clicking the table headers) instead of the default sort")
+    }
+    ui.checkbox("Alphabetical when unsorted", &mut state.flags.sort_alphabetical);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Sort by name instead of join order when the table isn't sorted by a column")
+    }
+    ui.checkbox("Squad members first", &mut state.flags.squad_first);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Show the players in the squad above the ones that aren't, e.g. with \"Show all\" checked.\nBoth groups keep the sort order")
+    }
+
+    ui.text("Shortcut modifiers:");
+    ui.same_line();
+    ui.checkbox("Ctrl##shortcut_modifier", &mut state.shortcut_modifiers.ctrl);
+    ui.same_line();
+    ui.checkbox("Alt##shortcut_modifier", &mut state.shortcut_modifiers.alt);
+    ui.same_line();
+    ui.checkbox("Shift##shortcut_modifier", &mut state.shortcut_modifiers.shift);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Keys that must be held for the shortcuts below.\nAlso set by holding them while setting a shortcut")
+    }
+    shortcut_setting(ui, state, Shortcut::ToggleWindow);
+    shortcut_setting(ui, state, Shortcut::AddLastLeaver);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Adds the last player who left the squad to the list")
+    }
+    shortcut_setting(ui, state, Shortcut::CyclePresets);
+    shortcut_setting(ui, state, Shortcut::ToggleShowAll);
+    shortcut_setting(ui, state, Shortcut::ClearFilters);
+    shortcut_setting(ui, state, Shortcut::FocusAddUser);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Opens the window and starts typing in the \"Add user\" box")
+    }
+
+    ui.separator();
+    ui.text("Filter presets:");
+    let mut delete_preset = None;
+    for (i, preset) in state.filter_presets.iter().enumerate() {
+        ui.text(&preset.name);
+        ui.same_line();
+        if ui.button(format!("X##delete_preset_{i}")) {
+            delete_preset = Some(i)
+        }
+    }
+    if let Some(i) = delete_preset {
+        state.filter_presets.remove(i);
+        state.current_preset = None;
+    }
+    ui.input_text("##preset_name", &mut state.preset_name_text).build();
+    ui.same_line();
+    if ui.button("Save current filters") {
+        let name = state.preset_name_text.trim();
+        if !name.is_empty() {
+            state.filter_presets.push(FilterPreset {
+                name: name.to_string(),
+                filters: state.filters.clone(),
+            });
+            state.preset_name_text = "".to_string();
+        }
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Save the current filters as a preset with the name written on the left")
+    }
+
+    ui.separator();
+    if ui.button("Reload from disk") {
+        state.request_bulk_action(BulkAction::ReloadConfig)
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Read {} again, e.g. after editing it by hand", config_path()))
+    }
+    ui.same_line();
+    if ui.button("Clear all comments") {
+        state.request_bulk_action(BulkAction::ClearAllComments)
+    }
+    if ui.button("Delete filtered") {
+        state.request_bulk_action(BulkAction::DeleteFiltered { include_commented: state.delete_commented })
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Delete the players currently shown in the table.\nCan be undone with \"Undo delete\"")
+    }
+    ui.same_line();
+    ui.checkbox("Including commented", &mut state.delete_commented);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Also delete players with a comment, rating, labels, color, or pinned or ignored.\nOtherwise, they are kept")
+    }
+    if ui.button("Merge duplicates") {
+        state.request_bulk_action(BulkAction::MergeDuplicates)
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Merge players whose names only differ in capitalization, e.g. added by hand")
+    }
+    ui.same_line();
+    if ui.button("Export CSV") {
+        state.options_status = Some(match export_csv(state.players.player_list.iter().filter(|player| !player.comment.is_empty()), EXPORT_PATH) {
+            Ok(count) => format!("Exported {count} players to {EXPORT_PATH}"),
+            Err(e) => {
+                let msg = format!("Couldn't export to {EXPORT_PATH}: {e}");
+                log(LogLevel::Error, &msg);
+                msg
+            }
+        });
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Write the players with a comment to {EXPORT_PATH}"))
+    }
+    ui.same_line();
+    if ui.button("Import CSV") {
+        state.options_status = Some(match import_csv(&mut state.players, state.flags.import_overwrite, &state.self_name) {
+            Ok((imported, skipped, refused)) => import_status(imported, skipped, refused, "malformed rows"),
+            Err(e) => {
+                let msg = format!("Couldn't import {IMPORT_PATH}: {e}");
+                log(LogLevel::Error, &msg);
+                msg
+            }
+        });
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Add the players in {IMPORT_PATH} (name,comment columns) to the list"))
+    }
+    ui.same_line();
+    ui.checkbox("Overwrite comments", &mut state.flags.import_overwrite);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Imported comments replace the existing ones instead of being appended to them")
+    }
+    if ui.button("Export JSON") {
+        state.options_status = Some(match export_json(state.players.player_list.iter(), JSON_PATH) {
+            Ok(count) => format!("Exported {count} players to {JSON_PATH}"),
+            Err(e) => {
+                let msg = format!("Couldn't export to {JSON_PATH}: {e}");
+                log(LogLevel::Error, &msg);
+                msg
+            }
+        });
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Write every player, with their labels, colors, etc., to {JSON_PATH}"))
+    }
+    ui.same_line();
+    if ui.button("Import JSON") {
+        state.options_status = Some(match import_json(&mut state.players, JSON_PATH, state.flags.import_overwrite, &state.self_name) {
+            Ok((imported, skipped, refused)) => import_status(imported, skipped, refused, "without name or comment"),
+            Err(e) => {
+                let msg = format!("Couldn't import {JSON_PATH}: {e}");
+                log(LogLevel::Error, &msg);
+                msg
+            }
+        });
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Add the players in {JSON_PATH} to the list, as written by \"Export JSON\""))
+    }
+    if ui.button("Export visible CSV") {
+        let visible = state.visible_indices();
+        state.options_status = Some(match export_csv(visible.iter().map(|i| &state.players.player_list[*i]), VISIBLE_EXPORT_PATH) {
+            Ok(count) => format!("Exported {count} players to {VISIBLE_EXPORT_PATH}"),
+            Err(e) => {
+                let msg = format!("Couldn't export to {VISIBLE_EXPORT_PATH}: {e}");
+                log(LogLevel::Error, &msg);
+                msg
+            }
+        });
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Write the players shown in the table, in the same order, to {VISIBLE_EXPORT_PATH}"))
+    }
+    ui.same_line();
+    if ui.button("Export visible JSON") {
+        let visible = state.visible_indices();
+        state.options_status = Some(match export_json(visible.iter().map(|i| &state.players.player_list[*i]), VISIBLE_JSON_PATH) {
+            Ok(count) => format!("Exported {count} players to {VISIBLE_JSON_PATH}"),
+            Err(e) => {
+                let msg = format!("Couldn't export to {VISIBLE_JSON_PATH}: {e}");
+                log(LogLevel::Error, &msg);
+                msg
+            }
+        });
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("Write the players shown in the table, in the same order, to {VISIBLE_JSON_PATH}.\nIt can be imported by renaming it to {JSON_PATH}"))
+    }
+    ui.input_text_multiline("##paste_names", &mut state.paste_names, [300.0, 60.0]).build();
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Account names separated by new lines or commas, e.g. a roster copied from Discord")
+    }
+    ui.same_line();
+    if ui.button("Add all") {
+        if split_names(&state.paste_names).count() > PASTE_CONFIRM_COUNT {
+            // Most likely pasted by mistake, so it's confirmed even with confirm_bulk_actions off
+            state.ask_confirmation(BulkAction::AddPastedNames);
+        } else {
+            state.perform_bulk_action(BulkAction::AddPastedNames);
+        }
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Add every pasted name to the list.\nNames that aren't account names (Name.1234) are skipped, unless \"Any name\" is checked next to \"Add\"")
+    }
+    if ui.button("Find duplicate comments") {
+        state.shared_comments = Some(state.players.shared_comments())
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("List the comments that several players have, e.g. pasted on the wrong players.\nNothing is changed")
+    }
+    if state.shared_comments.is_some() {
+        ui.same_line();
+        if ui.button("Hide##shared_comments") {
+            state.shared_comments = None
+        }
+    }
+    match &state.shared_comments {
+        Some(shared_comments) if shared_comments.is_empty() => ui.text("Every comment is unique"),
+        Some(shared_comments) => {
+            ui.child_window("##shared_comments").size([0.0, 150.0]).border(true).build(|| {
+                for (comment, names) in shared_comments.iter() {
+                    ui.text_wrapped(comment);
+                    ui.text_disabled(format!("{} players: {}", names.len(), names.join(", ")));
+                    ui.separator();
+                }
+            });
+        },
+        None => (),
+    }
+    ui.checkbox("Confirm bulk actions", &mut state.flags.confirm_bulk_actions);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Ask for confirmation before actions that affect many players at once")
+    }
+    ui.checkbox("Add squad members", &mut state.flags.auto_add);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Add everyone that joins the squad to the list.\nOtherwise only the players added by hand are shown as in the squad")
+    }
+    ui.checkbox("Keep all players", &mut state.players.keep_all);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Never remove players without a comment when they or you leave the squad.\nEveryone you group with is saved, so the config keeps growing")
+    }
+    if state.players.keep_all {
+        let mut keep_all_limit = state.players.keep_all_limit.min(i32::MAX as u32) as i32;
+        if ui.input_int("Max saved players without comment", &mut keep_all_limit).build() {
+            state.players.keep_all_limit = keep_all_limit.max(0) as u32
+        }
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Only the most recently seen ones are saved. 0 means no limit")
+        }
+    }
+    let mut max_players = state.players.max_players.min(i32::MAX as u32) as i32;
+    if ui.input_int("Max players", &mut max_players).build() {
+        state.players.max_players = max_players.max(0) as u32
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Players can't be added by hand once the list has this many, so a mistaken paste can't bloat the config.\nSquad members are still added. 0 means no limit")
+    }
+    ui.checkbox("Confirm removal on leave", &mut state.flags.confirm_wipe);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("When you leave the squad, ask before removing the players without a comment.\nThey are removed anyway if you don't answer in 2 minutes or before joining another squad")
+    }
+    if ui.checkbox("File queries", &mut state.flags.file_query) {
+        // Written again even if the list didn't change, in case the file was deleted meanwhile
+        state.last_roster.clear()
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text(format!("For external tools.\nThe list is written to {ROSTER_PATH}\nAccount names written to {QUERY_PATH} (one per line) are answered in {QUERY_RESPONSE_PATH}"))
+    }
+
+    ui.checkbox("Show diagnostics", &mut state.show_diagnostics);
+    if state.show_diagnostics {
+        let players = &state.players;
+        ui.text(format!("Players: {}, names: {}, undoable deletions: {}",
+            players.player_list.len(), players.name_dict.len(), players.last_deleted.len()));
+        let problems = players.validate();
+        if problems.is_empty() {
+            ui.text("No inconsistencies found");
+        }
+        for problem in problems.iter() {
+            ui.text_colored(ERROR_COLOR, problem);
+        }
+    }
+
+    if ui.is_any_item_active() {
+        state.dirty = true
+    }
+    if let Some(status) = &state.options_status {
+        ui.text(status);
+    }
+}
+
+fn shortcut_setting(ui: &Ui, state: &mut State, shortcut: Shortcut) {
+    let id = shortcut.id();
+    match state.shortcut_keys.get(&shortcut) {
+        Some(&c) => ui.text(format!("{}: {}{}", shortcut.name(), state.shortcut_modifiers.text(), vk_to_text(c))),
+        None => ui.text(format!("{}: No shortcut set", shortcut.name())),
+    }
+
+    ui.same_line();
+    if ui.button(format!("X##{id}")) {
+        state.shortcut_keys.remove(&shortcut);
+    }
+
+    if state.listening_to_key == Some(shortcut) {
+        ui.same_line();
+        ui.text("Listening ... ");
+        ui.same_line();
+        if ui.button(format!("Cancel##{id}")) {
+            state.listening_to_key = None;
+            state.shortcut_keys.remove(&shortcut);
+        }
+    } else {
+        ui.same_line();
+        if ui.button(format!("Set shortcut##{id}")) {
+            state.listening_to_key = Some(shortcut)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum LogLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Warning => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+/// Appends `msg` to LOG_PATH, prefixed with the unix time and `level`. Once LOG_PATH reaches
+/// LOG_MAX_SIZE, it's moved to OLD_LOG_PATH and a new one is started.
+/// Logging must never crash the game, so any error is ignored
+fn log(level: LogLevel, msg: &str) {
+    let _ = create_parent_dir(LOG_PATH);
+    if std::fs::metadata(LOG_PATH).map_or(false, |metadata| metadata.len() >= LOG_MAX_SIZE) {
+        let _ = std::fs::rename(LOG_PATH, OLD_LOG_PATH);
+    }
+    if let Ok(mut file) = File::options().create(true).append(true).open(LOG_PATH) {
+        let _ = writeln!(file, "{} [{}] {msg}", unix_now(), level.name());
+    }
+}
+
+/// Called from nofilter when shortcut_modifiers are held. Does nothing while typing in
+/// a text input, where the keys are meant for the text
+fn shortcuts(state: &mut State, key: usize, key_down: bool, holding_key: bool) -> bool {
+    if state.typing {
+        return true
+    }
+    if key_down && !holding_key {
+        let shortcut = state.shortcut_keys.iter()
+            .find(|(_, vk)| key == vk.0 as usize)
+            .map(|(shortcut, _)| *shortcut);
+        if let Some(shortcut) = shortcut {
+            state.run_shortcut(shortcut);
+            return false
         }
-        _ => None
-    };
+    }
+
+    true
+}
 
+fn nofilter(key: usize, key_down: bool, holding_key: bool) -> bool {
     let mut state = get_state();
-    state.players = player_list;
-    state.flags.display_window = display_window;
-    state.flags.show_all = show_all;
-    state.inactive_color = inactive_color;
-    state.comment_size = comment_size;
-    state.shortcut_char = shortcut_char;
+    if state.held_modifiers.update(key, key_down) {
+        return true
+    }
+    if key_down && !holding_key {
+        if let Some(shortcut) = state.listening_to_key.take() {
+            // A key only triggers one shortcut, so it's taken from the one that had it
+            state.shortcut_keys.retain(|other, vk| *other == shortcut || vk.0 != key as i32);
+            state.shortcut_keys.insert(shortcut, VirtualKey(key as i32));
+            // Keep the previous modifiers if none were held, so shortcuts don't trigger while typing
+            if state.held_modifiers != Modifiers::NONE {
+                state.shortcut_modifiers = state.held_modifiers;
+            }
+            return false
+        }
+    }
+    if state.shortcut_modifiers != Modifiers::NONE && state.held_modifiers == state.shortcut_modifiers {
+        return shortcuts(&mut state, key, key_down, holding_key)
+    }
 
-    Ok(())
+    true
 }
 
-fn init_extras(_: ExtrasAddonInfo, self_name: Option<&str>) {
-    let mut state = get_state();
+/// Readable names of the keys shortcuts are likely bound to. Other keys are shown by their code
+const KEY_NAMES: &[(VirtualKey, &str)] = &[
+    (VirtualKey::A, "A"),
+    (VirtualKey::B, "B"),
+    (VirtualKey::C, "C"),
+    (VirtualKey::D, "D"),
+    (VirtualKey::E, "E"),
+    (VirtualKey::F, "F"),
+    (VirtualKey::G, "G"),
+    (VirtualKey::H, "H"),
+    (VirtualKey::I, "I"),
+    (VirtualKey::J, "J"),
+    (VirtualKey::K, "K"),
+    (VirtualKey::L, "L"),
+    (VirtualKey::M, "M"),
+    (VirtualKey::N, "N"),
+    (VirtualKey::O, "O"),
+    (VirtualKey::P, "P"),
+    (VirtualKey::Q, "Q"),
+    (VirtualKey::R, "R"),
+    (VirtualKey::S, "S"),
+    (VirtualKey::T, "T"),
+    (VirtualKey::U, "U"),
+    (VirtualKey::V, "V"),
+    (VirtualKey::W, "W"),
+    (VirtualKey::X, "X"),
+    (VirtualKey::Y, "Y"),
+    (VirtualKey::Z, "Z"),
+    (VirtualKey::Number0, "0"),
+    (VirtualKey::Number1, "1"),
+    (VirtualKey::Number2, "2"),
+    (VirtualKey::Number3, "3"),
+    (VirtualKey::Number4, "4"),
+    (VirtualKey::Number5, "5"),
+    (VirtualKey::Number6, "6"),
+    (VirtualKey::Number7, "7"),
+    (VirtualKey::Number8, "8"),
+    (VirtualKey::Number9, "9"),
+    (VirtualKey::F1, "F1"),
+    (VirtualKey::F2, "F2"),
+    (VirtualKey::F3, "F3"),
+    (VirtualKey::F4, "F4"),
+    (VirtualKey::F5, "F5"),
+    (VirtualKey::F6, "F6"),
+    (VirtualKey::F7, "F7"),
+    (VirtualKey::F8, "F8"),
+    (VirtualKey::F9, "F9"),
+    (VirtualKey::F10, "F10"),
+    (VirtualKey::F11, "F11"),
+    (VirtualKey::F12, "F12"),
+    (VirtualKey::F13, "F13"),
+    (VirtualKey::F14, "F14"),
+    (VirtualKey::F15, "F15"),
+    (VirtualKey::F16, "F16"),
+    (VirtualKey::F17, "F17"),
+    (VirtualKey::F18, "F18"),
+    (VirtualKey::F19, "F19"),
+    (VirtualKey::F20, "F20"),
+    (VirtualKey::F21, "F21"),
+    (VirtualKey::F22, "F22"),
+    (VirtualKey::F23, "F23"),
+    (VirtualKey::F24, "F24"),
+    (VirtualKey::NumberPad0, "Numpad 0"),
+    (VirtualKey::NumberPad1, "Numpad 1"),
+    (VirtualKey::NumberPad2, "Numpad 2"),
+    (VirtualKey::NumberPad3, "Numpad 3"),
+    (VirtualKey::NumberPad4, "Numpad 4"),
+    (VirtualKey::NumberPad5, "Numpad 5"),
+    (VirtualKey::NumberPad6, "Numpad 6"),
+    (VirtualKey::NumberPad7, "Numpad 7"),
+    (VirtualKey::NumberPad8, "Numpad 8"),
+    (VirtualKey::NumberPad9, "Numpad 9"),
+    (VirtualKey::Multiply, "Numpad *"),
+    (VirtualKey::Add, "Numpad +"),
+    (VirtualKey::Subtract, "Numpad -"),
+    (VirtualKey::Decimal, "Numpad ."),
+    (VirtualKey::Divide, "Numpad /"),
+    (VirtualKey::Left, "Left"),
+    (VirtualKey::Up, "Up"),
+    (VirtualKey::Right, "Right"),
+    (VirtualKey::Down, "Down"),
+    (VirtualKey::Home, "Home"),
+    (VirtualKey::End, "End"),
+    (VirtualKey::PageUp, "Page Up"),
+    (VirtualKey::PageDown, "Page Down"),
+    (VirtualKey::Insert, "Insert"),
+    (VirtualKey::Delete, "Delete"),
+    (VirtualKey::Space, "Space"),
+    (VirtualKey::Enter, "Enter"),
+    (VirtualKey::Tab, "Tab"),
+    (VirtualKey::Escape, "Escape"),
+    (VirtualKey::Back, "Backspace"),
+    (VirtualKey::Pause, "Pause"),
+    (VirtualKey::Scroll, "Scroll Lock"),
+    (VirtualKey::Snapshot, "Print Screen"),
+];
 
-    if let Some(self_name) = self_name {
-        state.flags.extras_initialized = true;
-        state.self_name = self_name.to_owned();
+fn vk_to_text(vk: VirtualKey) -> String {
+    match KEY_NAMES.iter().find(|(key, _)| *key == vk) {
+        Some((_, name)) => name.to_string(),
+        None => format!("Key<{}>", vk.0),
     }
 }
 
-fn init_player_list(config: &mut Map<String, Value>) -> PlayerVecMap {
-    let players = config.remove(PLAYERS);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let players = match players {
-        Some(Value::Array(players)) => players,
-        _ => vec![],
-    };
+    fn player_list(names: &[&str]) -> PlayerVecMap {
+        let mut players = PlayerVecMap::new();
+        for name in names {
+            players.add_player(name, String::new(), false);
+        }
+        players
+    }
 
-    let mut player_map = HashMap::new();
+    fn names(players: &PlayerVecMap) -> Vec<&str> {
+        players.player_list.iter().map(|player| player.name.as_str()).collect()
+    }
 
-    let player_list: Vec<_> = players.into_iter()
-        .filter_map(|val| {
-            let mut properties = match val {
-                Value::Table(properties) => properties,
-                _ => return None
-            };
+    /// Tests that use the config files run one at a time, each in an empty directory
+    static CONFIG_LOCK: Mutex<()> = Mutex::new(());
 
-            let name = properties.remove("name");
-            let comment = properties.remove("comment");
+    fn temp_config(test: &str) -> MutexGuard<'static, ()> {
+        let guard = CONFIG_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let dir = std::env::temp_dir().join("player_list_tests").join(test);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // log() writes next to the working directory, like in the game
+        std::env::set_current_dir(&dir).unwrap();
+        std::env::set_var(CONFIG_PATH_VAR, dir.join("player_list.toml"));
+        guard
+    }
 
-            if let (Some(Value::String(name)), Some(Value::String(comment))) = (name, comment) {
-                Some(Player {
-                    lowercase_name: name.to_lowercase(),
-                    name,
-                    lowercase_comment: comment.to_lowercase(),
-                    comment,
-                    in_squad: false,
-                })
-            } else {
-                None
-            }
-        }).collect();
+    #[test]
+    fn user_left_removes_blank_players() {
+        let mut players = player_list(&["A.1234", "B.1234"]);
+        players.user_left("a.1234");
+        assert_eq!(names(&players), ["B.1234"]);
+        assert!(players.validate().is_empty());
+        // Not undoable, so an earlier delete can still be undone
+        assert!(players.last_deleted.is_empty());
+    }
 
-    for (i, player) in player_list.iter().enumerate() {
-        player_map.insert(player.name.clone(), i);
+    #[test]
+    fn user_left_keeps_commented_players() {
+        let mut players = player_list(&["A.1234"]);
+        players.set_comment("A.1234", "friend");
+        players.upsert("A.1234").in_squad = true;
+        players.user_left("A.1234");
+        let player = players.get("A.1234").unwrap();
+        assert!(!player.in_squad);
+        assert!(player.left_at.is_some());
+
+        players.keep_all = true;
+        players.add_player("B.1234", String::new(), false);
+        players.user_left("B.1234");
+        assert_eq!(names(&players), ["A.1234", "B.1234"]);
     }
 
-    PlayerVecMap {
-        player_list,
-        name_dict: player_map,
+    #[test]
+    fn user_left_ignores_missing_players() {
+        let mut players = player_list(&["A.1234"]);
+        players.user_left("Missing.1234");
+        assert_eq!(names(&players), ["A.1234"]);
     }
-}
 
-fn release() {
-    let mut config = Map::new();
+    #[test]
+    fn rejoining_keeps_the_comment() {
+        let mut players = player_list(&["A.1234"]);
+        players.set_comment("A.1234", "Friend");
+        players.upsert("A.1234").last_seen = Some(0);
+        players.join("a.1234", "raid");
+        let player = players.get("A.1234").unwrap();
+        assert!(player.in_squad);
+        assert!(player.last_seen.unwrap() > 0);
+        assert_eq!(player.comment, "Friend");
+        assert_eq!(player.lowercase_comment, "friend");
+        assert_eq!(player.contexts, ["raid"]);
+        assert_eq!(player.encounter_count, 1);
+        // Updates while in the squad aren't new encounters
+        players.join("A.1234", "");
+        assert_eq!(players.get("A.1234").unwrap().encounter_count, 1);
+        players.user_left("A.1234");
+        players.join("A.1234", "");
+        assert_eq!(players.get("A.1234").unwrap().encounter_count, 2);
+        assert_eq!(players.get("A.1234").unwrap().comment, "Friend");
+    }
 
-    let state = get_state();
-    let player_list = state.players.player_list.iter().filter_map(|player| {
-        if player.comment != "" {
-            Some(player.to_toml())
-        } else {
-            None
+    #[test]
+    fn undo_delete_restores_indices() {
+        let mut players = player_list(&["A.1234", "B.1234", "C.1234", "D.1234"]);
+        players.delete_where(|index, _| index == 1 || index == 3);
+        assert_eq!(names(&players), ["A.1234", "C.1234"]);
+        assert!(players.validate().is_empty());
+        players.undo_delete();
+        assert_eq!(names(&players), ["A.1234", "B.1234", "C.1234", "D.1234"]);
+        assert!(players.validate().is_empty());
+    }
+
+    #[test]
+    fn indices_stay_right_in_large_lists() {
+        let mut players = PlayerVecMap::new();
+        for i in 0..5000 {
+            players.add_player(&format!("Player{i}.1234"), if i % 3 == 0 { "kept".to_string() } else { String::new() }, false);
         }
-    }).collect();
-    config.insert(PLAYERS.to_string(), Value::Array(player_list));
-    config.insert(OPENED_WINDOW.to_string(), Value::Boolean(state.flags.display_window));
-    let inactive_color = state.inactive_color.into_iter()
-        .map(|val| Value::Float(val as f64)).collect();
-    config.insert(INACTIVE_COLOR.to_string(), Value::Array(inactive_color));
-    let comment_size = state.comment_size.into_iter()
-        .map(|val| Value::Float(val as f64)).collect();
-    config.insert(COMMENT_SIZE.to_string(), Value::Array(comment_size));
-    config.insert(SHOW_ALL.to_string(), Value::Boolean(state.flags.show_all));
-    if let Some(i) = state.shortcut_char {
-        config.insert(SHORTCUT.to_string(), Value::Integer(i.0 as i64));
+        players.delete_where(|index, _| index % 2 == 0);
+        players.delete("Player1.1234");
+        players.user_left("Player4999.1234");
+        players.delete_all();
+        assert!(players.validate().is_empty());
+        for player in players.player_list.iter() {
+            assert_eq!(player.comment, "kept");
+            assert_eq!(players.get(&player.name).unwrap().name, player.name);
+        }
+        // Odd multiples of 3 below 5000
+        assert_eq!(players.player_list.len(), 833);
     }
 
-    let toml_string = toml::to_string(&Value::Table(config)).unwrap();
-    std::fs::write(CONFIG_PATH, toml_string).unwrap()
-}
+    #[test]
+    fn undo_delete_merges_into_players_added_again() {
+        let mut players = player_list(&["A.1234", "B.1234"]);
+        players.set_comment("B.1234", "healer");
+        players.delete("B.1234");
+        players.add_player("b.1234", String::new(), false);
+        players.undo_delete();
+        assert_eq!(names(&players), ["A.1234", "b.1234"]);
+        assert_eq!(players.get("B.1234").unwrap().comment, "healer");
+    }
 
-fn get_state<'a>() -> MutexGuard<'a, State>{
-    unsafe{STATE.lock().unwrap()}
-}
+    #[test]
+    fn delete_row_tells_duplicates_apart() {
+        let mut players = player_list(&["A.1234"]);
+        players.player_list.push(Player::new("a.1234", "old".to_string(), false));
+        players.rebuild_name_dict();
+        players.delete_row(0, "A.1234");
+        assert_eq!(names(&players), ["a.1234"]);
+        // A stale index falls back to the name
+        players.delete_row(5, "a.1234");
+        assert!(players.player_list.is_empty());
+    }
 
-fn squad_update(users: UserInfoIter) {
-    for user in users {
-        if let Some(username) = user.account_name {
-            match user.role {
-                arcdps::extras::UserRole::None => remove_user(username),
-                _ => add_user(username),
-            }
+    #[test]
+    fn comment_filter_matches_new_players() {
+        let mut state = State::new();
+        state.flags.show_all = true;
+        state.players.add_player("A.1234", "Comment Here".to_string(), true);
+        state.players.add_player("B.1234", String::new(), true);
+        state.filters.comment_filter_str = "comment here".to_string();
+        assert_eq!(state.visible_indices(), [0]);
+    }
+
+    #[test]
+    fn edited_comments_are_found_by_the_filter() {
+        let mut state = State::new();
+        state.players = player_list(&["A.1234", "B.1234"]);
+        state.flags.show_all = true;
+        state.filters.comment_filter_str = "healer".to_string();
+        assert!(state.visible_indices().is_empty());
+        // Typed into the comment box, which writes the text directly, and closed the window while editing
+        state.players.upsert("B.1234").comment = "Good HEALER".to_string();
+        state.editing_comment = Some("B.1234".to_string());
+        state.finish_comment_edit();
+        assert_eq!(state.visible_indices(), [1]);
+        assert!(state.dirty);
+        assert!(state.editing_comment.is_none());
+    }
+
+    #[test]
+    fn delete_and_add_in_the_same_frame() {
+        let mut players = player_list(&["A.1234", "B.1234"]);
+        // Both deletes are queued while drawing, and a player joins before they are applied
+        let actions = [(0, "A.1234"), (1, "B.1234")];
+        players.add_player("C.1234", String::new(), false);
+        for (index, name) in actions {
+            players.delete_row(index, name);
         }
+        assert_eq!(names(&players), ["C.1234"]);
+        assert!(players.validate().is_empty());
+        // Stale indices past the end don't panic
+        players.delete_row(10, "Missing.1234");
+        assert_eq!(names(&players), ["C.1234"]);
     }
-}
 
-fn remove_user(username: &str) {
-    let mut state = get_state();
+    #[test]
+    fn delete_while_sorted() {
+        let mut state = State::new();
+        state.players = player_list(&["C.1234", "A.1234", "B.1234"]);
+        state.flags.show_all = true;
+        state.flags.sort_alphabetical = true;
+        let shown = state.visible_indices();
+        assert_eq!(shown, [1, 2, 0]);
+        // The first row shown
+        let name = state.players.player_list[shown[0]].name.clone();
+        state.players.delete_row(shown[0], &name);
+        assert_eq!(names(&state.players), ["C.1234", "B.1234"]);
+        assert_eq!(state.visible_indices(), [1, 0]);
+    }
 
-    let is_self = username == state.self_name;
+    #[test]
+    fn delete_while_filtered() {
+        let mut state = State::new();
+        state.players = player_list(&["Bob.1234", "Alice.1234"]);
+        // A duplicate from an old config
+        state.players.player_list.push(Player::new("bob.1234", "old".to_string(), false));
+        state.players.rebuild_name_dict();
+        state.flags.show_all = true;
+        state.filters.user_filter_str = "bob".to_string();
+        let shown = state.visible_indices();
+        assert_eq!(shown, [0, 2]);
+        let name = state.players.player_list[shown[1]].name.clone();
+        state.players.delete_row(shown[1], &name);
+        assert_eq!(names(&state.players), ["Bob.1234", "Alice.1234"]);
+        assert_eq!(state.visible_indices(), [0]);
+    }
 
-    if is_self {
-        state.players.delete_all()
-    } else {
-        state.players.user_left(username);
+    #[test]
+    fn removing_a_duplicate_keeps_the_other() {
+        let mut players = player_list(&["A.1234", "B.1234"]);
+        players.player_list.push(Player::new("a.1234", "old".to_string(), false));
+        players.rebuild_name_dict();
+        players.delete("A.1234");
+        assert_eq!(names(&players), ["A.1234", "B.1234"]);
+        assert!(players.get("a.1234").is_some());
+        assert_eq!(players.duplicate_count(), 0);
+        assert!(players.validate().is_empty());
+        players.user_left("A.1234");
+        assert_eq!(names(&players), ["B.1234"]);
+        assert!(players.validate().is_empty());
     }
-}
 
-fn add_user(username: &str) {
-    let mut state = get_state();
+    #[test]
+    fn delete_filtered_only_deletes_the_shown_players() {
+        let mut state = State::new();
+        state.players = player_list(&["Anna.1234", "Alex.1234", "Bob.1234"]);
+        state.players.set_comment("Alex.1234", "friend");
+        state.flags.show_all = true;
+        state.filters.user_filter_str = "a".to_string();
+        state.flags.confirm_bulk_actions = true;
+        state.request_bulk_action(BulkAction::DeleteFiltered { include_commented: false });
+        assert_eq!(state.players.player_list.len(), 3);
+        state.pending_bulk_action = None;
+        state.perform_bulk_action(BulkAction::DeleteFiltered { include_commented: false });
+        assert_eq!(names(&state.players), ["Alex.1234", "Bob.1234"]);
+        state.perform_bulk_action(BulkAction::DeleteFiltered { include_commented: true });
+        assert_eq!(names(&state.players), ["Bob.1234"]);
+        assert!(state.players.validate().is_empty());
+    }
 
-    let is_self = username == state.self_name;
+    #[test]
+    fn rename() {
+        let mut players = player_list(&["A.1234", "B.1234"]);
+        assert!(players.rename(0, "A.1234", "C.1234").is_ok());
+        assert!(players.get("A.1234").is_none());
+        assert_eq!(players.get("c.1234").unwrap().name, "C.1234");
+        assert!(players.rename(0, "C.1234", "c.1234").is_ok());
+        assert_eq!(players.get("C.1234").unwrap().name, "c.1234");
+        assert!(players.rename(0, "c.1234", "b.1234").is_err());
+        assert!(players.rename(0, "c.1234", "").is_err());
+        assert!(players.rename(0, "c.1234", &"a".repeat(MAX_NAME_LEN + 1)).is_err());
+        assert!(players.rename(5, "Missing.1234", "D.1234").is_err());
+        // A stale index falls back to the name
+        assert!(players.rename(5, "B.1234", "D.1234").is_ok());
+        assert_eq!(names(&players), ["c.1234", "D.1234"]);
+        assert!(players.validate().is_empty());
+    }
 
-    if !is_self {
-        state.players.join(username);
+    #[test]
+    fn rename_the_clicked_duplicate() {
+        let mut players = player_list(&["A.1234"]);
+        players.player_list.push(Player::new("a.1234", "old".to_string(), false));
+        players.rebuild_name_dict();
+        assert!(players.rename(0, "A.1234", "B.1234").is_ok());
+        assert_eq!(names(&players), ["B.1234", "a.1234"]);
+        assert_eq!(players.get("a.1234").unwrap().comment, "old");
+        assert!(players.validate().is_empty());
     }
-}
 
-fn draw_window(ui: &Ui, not_character_or_loading: bool) {
-    let state = get_state();
-    if !not_character_or_loading {
-        // Don't draw anything on character screen or loading screen
-        return
+    #[test]
+    fn rename_checks_like_adding() {
+        let mut state = State::new();
+        state.players = player_list(&["A.1234"]);
+        state.self_name = "Me.1234".to_string();
+        assert!(state.rename_player(0, "A.1234", "me.1234").is_err());
+        assert!(state.rename_player(0, "A.1234", "Not an account").is_err());
+        state.add_any_name = true;
+        assert!(state.rename_player(0, "A.1234", "Not an account").is_ok());
     }
 
-    if !state.flags.extras_initialized {
-        arcdps::imgui::Window::new("Player List Error").collapsible(false).build(ui, || {
-            ui.text("Unofficial extras extension required")
-        });
+    #[test]
+    fn merge_duplicates_into_the_first() {
+        let mut players = player_list(&["A.1234", "B.1234"]);
+        players.player_list.push(Player::new("a.1234", "tank".to_string(), false));
+        players.rebuild_name_dict();
+        assert_eq!(players.duplicate_count(), 1);
+        assert_eq!(players.merge_duplicates(), 1);
+        assert_eq!(names(&players), ["A.1234", "B.1234"]);
+        assert_eq!(players.get("a.1234").unwrap().comment, "tank");
+        assert!(players.validate().is_empty());
+    }
 
-        return
-    };
+    #[test]
+    fn csv_round_trip() {
+        let fields = ["plain", "with, comma", "with \"quotes\"", "multi\nline", ""];
+        let csv: String = fields.iter()
+            .map(|field| format!("{},{}\n", csv_field("Name.1234"), csv_field(field)))
+            .collect();
+        let records = parse_csv(&csv);
+        assert_eq!(records.len(), fields.len());
+        for (record, field) in records.into_iter().zip(fields) {
+            assert_eq!(record, Some(vec!["Name.1234".to_string(), field.to_string()]));
+        }
+        assert_eq!(parse_csv("a,\"unterminated\n"), vec![None]);
+    }
 
-    let mut opened_window = state.flags.display_window;
-    std::mem::drop(state); // liberates the mutex so get_state() can be called again from the closure in .build()
-    if opened_window {
-        arcdps::imgui::Window::new("Player List").opened(&mut opened_window).collapsible(false).build(ui, || {
-            let column_data = [
-                // max character length of account name = 32 characters
-                TableColumnSetup {
-                    name: "name",
-                    ..Default::default()
-                },
-                TableColumnSetup {
-                    name: "comment",
-                    ..Default::default()
-                }
-            ];
-            {
-                let mut state = get_state();
-                let state = state.deref_mut();
-                ui.checkbox("Show all", &mut state.flags.show_all);
+    #[test]
+    fn json_round_trip() {
+        let mut players = player_list(&["Zoë Ørn.1234", "B.1234"]);
+        let comment = "friend, \"tank\"\n\\ ünïcödé 🛡";
+        let player = players.upsert("Zoë Ørn.1234");
+        player.comment = comment.to_string();
+        player.labels.push("tank \"main\"".to_string());
+        player.rating = 4;
+        player.pinned = true;
+        player.encounter_count = 3;
+        let path = std::env::temp_dir().join("player_list_test.json");
+        let path = path.to_str().unwrap();
+        assert_eq!(export_json(players.player_list.iter(), path), Ok(2));
 
-                ui.separator();
-                ui.text("Add user:");
-                ui.input_text("##add_user", &mut state.add_user_text).build();
-                ui.same_line();
-                if ui.button("Add") {
-                    if !state.add_user_text.is_empty() {
-                        state.players.add_player(&state.add_user_text, "Comment here".to_string());
-                        state.add_user_text = "".to_string();
-                    }
-                };
+        let mut imported = PlayerVecMap::new();
+        assert_eq!(import_json(&mut imported, path, false, ""), Ok((2, 0, 0)));
+        assert_eq!(names(&imported), ["Zoë Ørn.1234", "B.1234"]);
+        let player = imported.get("zoë ørn.1234").unwrap();
+        assert_eq!(player.comment, comment);
+        assert_eq!(player.labels, ["tank \"main\""]);
+        assert_eq!(player.rating, 4);
+        assert!(player.pinned);
+        assert_eq!(player.encounter_count, 3);
+        // Imported players count as added by hand, like with the CSV import
+        assert!(imported.get("B.1234").unwrap().added_manually);
 
-                ui.separator();
-                ui.text("Filters:");
-                if ui.input_text("##user_filter", &mut state.filters.user_filter_str).build() {
-                    state.filters.user_filter_str = state.filters.user_filter_str.to_lowercase()
-                };
-                if ui.is_item_hovered() {
-                    ui.tooltip_text("Filter by user name")
-                }
-                if ui.input_text("##comment_filter", &mut state.filters.comment_filter_str).build() {
-                    state.filters.comment_filter_str = state.filters.comment_filter_str.to_lowercase()
-                };
-                if ui.is_item_hovered() {
-                    ui.tooltip_text("Filter by comment")
-                }
-            }
-            let mut action = None;
-            if let Some(table) = ui.begin_table_header("PLayerListTable", column_data) {
-                let mut state = get_state();
-                let state = state.deref_mut();
-                let filters = &state.filters;
-                let players = &mut state.players;
-                for (i, player) in players.player_list.iter_mut().enumerate() {
-                    if !filters.user_filter_str.is_empty() && !player.lowercase_name.starts_with(&filters.user_filter_str) {
-                        continue;
-                    }
-                    if !filters.comment_filter_str.is_empty() && !player.lowercase_comment.starts_with(&filters.comment_filter_str) {
-                        continue;
-                    }
-                    if !state.flags.show_all && !player.in_squad {
-                        continue;
-                    }
-                    ui.table_next_column();
-                    if ui.button(format!("X##delete_{i}")) {
-                        action = Some(Action::DeletePlayer(player.name.clone()))
-                    }
-                    if ui.is_item_hovered() {
-                        ui.tooltip_text("Delete this player\nfrom the list")
-                    }
-                    ui.same_line();
-                    if player.in_squad {
-                        ui.text(&player.name);
-                    } else {
-                        ui.text_colored(state.inactive_color, &player.name)
-                    }
+        // Importing the same list again doesn't count the encounters twice
+        assert_eq!(import_json(&mut imported, path, false, ""), Ok((2, 0, 0)));
+        assert_eq!(imported.get("Zoë Ørn.1234").unwrap().encounter_count, 3);
+        let _ = std::fs::remove_file(path);
+    }
 
-                    ui.table_next_column();
-                    if ui.input_text_multiline(format!("##{i}"), &mut player.comment, state.comment_size).build() {
-                        player.lowercase_comment = player.comment.to_lowercase()
-                    };
-                }
-                table.end()
-            };
+    #[test]
+    fn config_is_written_through_the_temp_file() {
+        let _config = temp_config("write_config");
+        write_config("Players = []\n").unwrap();
+        assert_eq!(std::fs::read_to_string(config_path()).unwrap(), "Players = []\n");
+        write_config("KeepAll = true\n").unwrap();
+        assert_eq!(std::fs::read_to_string(config_path()).unwrap(), "KeepAll = true\n");
+        assert!(!Path::new(&config_sibling_path(TMP_EXTENSION)).exists());
+    }
 
-            if let Some(action) = action {
-                match action {
-                    Action::DeletePlayer(username) => get_state().players.delete(&username),
-                }
-            }
-        });
+    #[test]
+    fn corrupt_config_falls_back_to_the_backup() {
+        let _config = temp_config("backup");
+        std::fs::write(config_path(), "Players = [").unwrap();
+        std::fs::write(backup_path(2), "[[Players]]\nname = \"A.1234\"\ncomment = \"older\"\n").unwrap();
+        std::fs::write(backup_path(1), "[[Players]]\nname = \"A.1234\"\ncomment = \"from backup\"\n").unwrap();
+        init().unwrap();
+        let state = get_state();
+        assert_eq!(state.players.get("A.1234").unwrap().comment, "from backup");
+        // Moved aside instead of being saved over
+        assert!(!Path::new(&config_path()).exists());
+        assert!(!state.flags.save_disabled);
     }
 
-    get_state().flags.display_window = opened_window;
-}
+    #[test]
+    fn window_flag_transitions() {
+        let mut state = State::new();
+        assert!(!state.flags.display_window);
+        state.run_shortcut(Shortcut::ToggleWindow);
+        assert!(state.flags.display_window && state.dirty);
+        state.run_shortcut(Shortcut::ToggleWindow);
+        assert!(!state.flags.display_window);
+        state.run_shortcut(Shortcut::FocusAddUser);
+        assert!(state.flags.display_window && state.focus_add_user);
 
-enum Action {
-    DeletePlayer(String)
-}
+        // Saved for the account it was toggled on
+        state.self_name = "Me.1234".to_string();
+        let config = config_from_state(&state);
+        assert_eq!(config.get(OPENED_WINDOW), Some(&Value::Boolean(true)));
+        let mut loaded = State::new();
+        load_config(&mut loaded, config);
+        assert!(loaded.flags.display_window);
+        // Closed on another account, which keeps it closed
+        loaded.flags.display_window = false;
+        loaded.self_name = "Other.1234".to_string();
+        loaded.apply_profile_display_window();
+        assert!(!loaded.flags.display_window);
+        loaded.self_name = "Me.1234".to_string();
+        loaded.apply_profile_display_window();
+        assert!(loaded.flags.display_window);
+    }
 
-fn options(ui: &Ui, window_name: Option<&str>) -> bool {
-    if let Some("error") = window_name {
-        ui.checkbox("player list", &mut get_state().flags.display_window);
+    #[test]
+    fn export_visible_matches_the_filters() {
+        let mut state = State::new();
+        state.players = player_list(&["Cara.1234", "Bob.1234", "Abe.1234", "Dan.1234"]);
+        state.players.upsert("Dan.1234").in_squad = true;
+        state.players.set_comment("Bob.1234", "good");
+        state.filters.quick_filter_str = "a".to_string();
+        state.flags.sort_alphabetical = true;
+        let visible = state.visible_indices();
+        // Out of the squad players are hidden without "Show all"
+        assert_eq!(visible, [3]);
+        state.flags.show_all = true;
+        let visible = state.visible_indices();
+
+        let path = std::env::temp_dir().join("player_list_visible_test.json");
+        let path = path.to_str().unwrap();
+        assert_eq!(export_json(visible.iter().map(|i| &state.players.player_list[*i]), path), Ok(3));
+        let mut exported = PlayerVecMap::new();
+        import_json(&mut exported, path, false, "").unwrap();
+        let _ = std::fs::remove_file(path);
+        // The ones matching, in the order shown
+        assert_eq!(names(&exported), ["Abe.1234", "Cara.1234", "Dan.1234"]);
+        let filters = &state.filters;
+        let matching = state.players.player_list.iter()
+            .filter(|player| filters.shows(player, "", "", "a", &state.flags))
+            .count();
+        assert_eq!(matching, 3);
     }
 
-    false
-}
+    #[test]
+    fn strip_discriminator_only_strips_account_numbers() {
+        assert_eq!(strip_discriminator("John Doe.1234"), "John Doe");
+        assert_eq!(strip_discriminator("John"), "John");
+        assert_eq!(strip_discriminator(".1234"), ".1234");
+        assert_eq!(strip_discriminator("John.12a4"), "John.12a4");
+        assert_eq!(strip_discriminator("John.12345"), "John.12345");
+    }
 
-fn options_tab(ui: &Ui) {
-    let mut state = get_state();
-    ColorEdit::new("Inactive player", &mut state.inactive_color).build(ui);
-    if ui.is_item_hovered() {
-        ui.tooltip_text("Color of the names of players out of the squad")
+    #[test]
+    fn account_names() {
+        assert!(is_account_name("John.1234"));
+        assert!(is_account_name("John Doe.1234"));
+        assert!(is_account_name("john_doe.0000"));
+        assert!(!is_account_name("John"));
+        assert!(!is_account_name("John.123"));
+        assert!(!is_account_name(" .1234"));
+        assert!(!is_account_name("Jo-hn.1234"));
     }
 
-    ui.input_float2("Comment Size", &mut state.comment_size).build();
+    #[test]
+    fn fuzzy_score_prefers_the_best_alignment() {
+        assert!(fuzzy_score("john doe.1234", "jdoe").is_some());
+        assert!(fuzzy_score("john doe", "xyz").is_none());
+        assert!(fuzzy_score("john doe", "eo").is_none());
+        // Greedily, "o" would be the one in "john"
+        assert_eq!(fuzzy_score("john doe", "oe"), Some(4));
+        assert!(fuzzy_score("john doe", "doe") > fuzzy_score("dxoxe", "doe"));
+    }
 
-    match state.shortcut_char {
-        Some(c) => ui.text(format!("Shortcut: {}", vk_to_text(c))),
-        None => ui.text("No shortcut set"),
+    #[test]
+    fn clamping() {
+        assert_eq!(clamp_column_split(f32::NAN), DEFAULT_COLUMN_SPLIT);
+        assert_eq!(clamp_column_split(0.0), COLUMN_SPLIT_MIN);
+        assert_eq!(clamp_column_split(1.0), COLUMN_SPLIT_MAX);
+        assert_eq!(clamp_column_split(0.3), 0.3);
+        assert_eq!(clamp_comment_size([f32::NAN, 50.0]), DEFAULT_COMMENT_SIZE);
+        assert_eq!(clamp_comment_size([0.0, 1e9]), [COMMENT_SIZE_MIN[0], COMMENT_SIZE_MAX[1]]);
+        assert_eq!(clamp_color([2.0, -1.0, 0.5, 1.0]), Some([1.0, 0.0, 0.5, 1.0]));
+        assert_eq!(clamp_color([f32::NAN, 0.0, 0.0, 1.0]), None);
     }
 
-    ui.same_line();
-    if ui.button("X") {
-        state.shortcut_char = None
+    #[test]
+    fn read_color_needs_4_floats() {
+        let color = |values: &[f64]| Some(Value::Array(values.iter().map(|val| Value::Float(*val)).collect()));
+        assert_eq!(read_color(color(&[0.25, 0.5, 0.75, 1.0])), Some([0.25, 0.5, 0.75, 1.0]));
+        assert_eq!(read_color(color(&[2.0, -1.0, 0.5, 1.0])), Some([1.0, 0.0, 0.5, 1.0]));
+        assert_eq!(read_color(color(&[0.5, 0.5, 0.5])), None);
+        assert_eq!(read_color(Some(Value::Array(vec![Value::Integer(1); 4]))), None);
+        assert_eq!(read_color(None), None);
     }
 
-    if state.listening_to_key {
-        ui.same_line();
-        ui.text("Listening ... ");
-        ui.same_line();
-        if ui.button("Cancel") {
-            state.listening_to_key = false;
-            state.shortcut_char = None
-        }
-    } else {
-        ui.same_line();
-        if ui.button("Set shortcut") {
-            state.listening_to_key = true
-        }
+    #[test]
+    fn rating_is_clamped_when_loaded() {
+        let rated = |rating: i64| {
+            let mut properties = Map::new();
+            properties.insert("name".to_string(), Value::String("A.1234".to_string()));
+            properties.insert("comment".to_string(), Value::String(String::new()));
+            properties.insert("rating".to_string(), Value::Integer(rating));
+            player_from_toml(Value::Table(properties)).unwrap().rating
+        };
+        assert_eq!(rated(3), 3);
+        assert_eq!(rated(9), MAX_RATING);
+        assert_eq!(rated(-3), 0);
+
+        let mut properties = Map::new();
+        properties.insert("min_rating".to_string(), Value::Integer(9));
+        assert_eq!(Filters::from_toml(&mut properties).min_rating, MAX_RATING);
     }
-}
 
-fn log(msg: &str) {
-    writeln!(File::options().create(true).append(true).open(TMP_PATH).unwrap(), "{msg}").unwrap();
-}
+    #[test]
+    fn min_rating_filter() {
+        let mut state = State::new();
+        state.players = player_list(&["A.1234", "B.1234", "C.1234"]);
+        state.players.upsert("A.1234").rating = 2;
+        state.players.upsert("B.1234").rating = 4;
+        state.flags.show_all = true;
+        assert_eq!(state.visible_indices(), [0, 1, 2]);
+        state.filters.min_rating = 2;
+        assert_eq!(state.visible_indices(), [0, 1]);
+        state.filters.min_rating = 3;
+        assert_eq!(state.visible_indices(), [1]);
+    }
 
-fn shortcuts(key: usize, key_down: bool, holding_key: bool) -> bool {
-    let mut state = get_state();
-    if key_down && !holding_key {
-        // Both modifier keys have been pressed
-        // modifiers are alt+shift by default
-        if let Some(c) = state.shortcut_char {
-            if key == c.0 as usize {
-                state.flags.display_window = !state.flags.display_window;
-                return false
-            }
+    #[test]
+    fn format_date_is_utc() {
+        assert_eq!(format_date(0, "{year}-{month}-{day}"), "1970-01-01");
+        assert_eq!(format_date(951782400, "{day}/{month}/{year}"), "29/02/2000");
+        assert_eq!(format_date(951782400 - 1, "{year}-{month}-{day}"), "2000-02-28");
+    }
+
+    #[test]
+    fn add_dated_note() {
+        let mut player = Player::new("A.1234", "Old".to_string(), false);
+        player.add_dated_note("  ", 0);
+        assert_eq!(player.comment, "Old");
+        player.add_dated_note(" Late ", 0);
+        assert_eq!(player.comment, "Old\n[1970-01-01] Late");
+        assert_eq!(player.lowercase_comment, "old\n[1970-01-01] late");
+    }
+
+    #[test]
+    fn snippets_append_to_the_focused_comment() {
+        let mut state = State::new();
+        state.players = player_list(&["A.1234", "B.1234"]);
+        state.snippets = vec!["Good healer".to_string(), "afk often".to_string()];
+        state.focused_comment = Some("a.1234".to_string());
+        for snippet in state.snippets.iter() {
+            state.players.append_comment(state.focused_comment.as_deref().unwrap(), snippet);
         }
+        let player = state.players.get("A.1234").unwrap();
+        assert_eq!(player.comment, "Good healer\nafk often");
+        assert_eq!(player.lowercase_comment, "good healer\nafk often");
+        assert_eq!(state.players.get("B.1234").unwrap().comment, "");
+
+        let mut loaded = State::new();
+        load_config(&mut loaded, config_from_state(&state));
+        assert_eq!(loaded.snippets, state.snippets);
     }
 
-    true
-}
+    #[test]
+    fn met_date_seeds_new_comments() {
+        let mut state = State::new();
+        state.flags.auto_add = true;
+        assert_eq!(state.new_player_comment(), DEFAULT_COMMENT);
+        state.flags.comment_met_date = true;
+        state.met_date_format = "met {year}-{month}-{day}".to_string();
+        let today = format_date(unix_now(), &state.met_date_format);
+        assert!(today.starts_with("met 20"));
+        assert_eq!(state.new_player_comment(), today);
 
-fn nofilter(key: usize, key_down: bool, holding_key: bool) -> bool {
-    let mut state = get_state();
-    if key_down && !holding_key && state.listening_to_key {
-        state.listening_to_key = false;
-        state.shortcut_char = Some(VirtualKey(key as i32));
-        return false
+        add_user(&mut state, "A.1234", None, false);
+        assert_eq!(state.players.get("A.1234").unwrap().comment, today);
+        // Only new players are seeded
+        state.players.add_player("B.1234", "Friend".to_string(), true);
+        add_user(&mut state, "B.1234", None, false);
+        assert_eq!(state.players.get("B.1234").unwrap().comment, "Friend");
     }
 
-    true
-}
+    #[test]
+    fn add_names() {
+        assert_eq!(split_names(" A.1234,\n\nB.1234 ,, ").collect::<Vec<_>>(), ["A.1234", "B.1234"]);
 
-fn vk_to_text(vk: VirtualKey) -> String {
-    match vk {
-        VirtualKey::A => "A".to_string(),
-        VirtualKey::B => "B".to_string(),
-        VirtualKey::C => "C".to_string(),
-        VirtualKey::D => "D".to_string(),
-        VirtualKey::E => "E".to_string(),
-        VirtualKey::F => "F".to_string(),
-        VirtualKey::G => "G".to_string(),
-        VirtualKey::H => "H".to_string(),
-        VirtualKey::I => "I".to_string(),
-        VirtualKey::J => "J".to_string(),
-        VirtualKey::K => "K".to_string(),
-        VirtualKey::L => "L".to_string(),
-        VirtualKey::M => "M".to_string(),
-        VirtualKey::N => "N".to_string(),
-        VirtualKey::O => "O".to_string(),
-        VirtualKey::P => "P".to_string(),
-        VirtualKey::Q => "Q".to_string(),
-        VirtualKey::R => "R".to_string(),
-        VirtualKey::S => "S".to_string(),
-        VirtualKey::T => "T".to_string(),
-        VirtualKey::U => "U".to_string(),
-        VirtualKey::V => "V".to_string(),
-        VirtualKey::W => "W".to_string(),
-        VirtualKey::X => "X".to_string(),
-        VirtualKey::Y => "Y".to_string(),
-        VirtualKey::Z => "Z".to_string(),
-        VirtualKey(key) => format!("Key<{key}>")
+        let mut players = player_list(&["Old.1234"]);
+        let text = "Me.1234, Not an account, old.1234\nNew.1234";
+        assert_eq!(super::add_names(&mut players, text, "met", false, "me.1234"), (1, 1, 1, None));
+        assert_eq!(names(&players), ["Old.1234", "New.1234"]);
+        assert_eq!(players.get("New.1234").unwrap().comment, "met");
+
+        players.max_players = 2;
+        let (added, _, skipped, refused) = super::add_names(&mut players, "Other.1234", "", true, "");
+        assert_eq!((added, skipped), (0, 1));
+        assert!(refused.is_some());
+    }
+
+    #[test]
+    fn check_new_player() {
+        let mut players = player_list(&["A.1234"]);
+        assert!(players.check_new_player("B.1234").is_ok());
+        assert!(players.check_new_player(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+        players.max_players = 1;
+        assert!(players.check_new_player("B.1234").is_err());
+        players.max_players = 0;
+        assert!(players.check_new_player("B.1234").is_ok());
+    }
+
+    #[test]
+    fn saved_limit_keeps_the_most_recently_seen() {
+        let mut players = player_list(&["A.1234", "B.1234", "C.1234", "D.1234"]);
+        players.upsert("A.1234").last_seen = Some(10);
+        players.upsert("B.1234").last_seen = Some(20);
+        players.set_comment("C.1234", "friend");
+        assert_eq!(players.saved(), [false, false, true, false]);
+        players.keep_all = true;
+        players.keep_all_limit = 1;
+        assert_eq!(players.saved(), [false, true, true, false]);
+        players.keep_all_limit = 0;
+        assert_eq!(players.saved(), [true, true, true, true]);
+    }
+
+    #[test]
+    fn pinned_order_survives_saving() {
+        let mut state = State::new();
+        state.players = player_list(&["A.1234", "B.1234", "C.1234", "D.1234"]);
+        state.players.upsert("C.1234").pinned = true;
+        state.players.upsert("B.1234").pinned = true;
+        state.players.set_comment("A.1234", "friend");
+        state.players.set_comment("D.1234", "friend");
+        // Pinned players are shown out of the squad even without "Show all"
+        assert_eq!(state.visible_indices(), [1, 2]);
+        state.flags.show_all = true;
+        assert_eq!(state.visible_indices(), [1, 2, 0, 3]);
+
+        // Saved in the order they are shown
+        let mut loaded = State::new();
+        load_config(&mut loaded, config_from_state(&state));
+        assert_eq!(names(&loaded.players), ["B.1234", "C.1234", "A.1234", "D.1234"]);
+        assert!(loaded.players.player_list[0].pinned && loaded.players.player_list[1].pinned);
+        assert_eq!(loaded.visible_indices(), [0, 1, 2, 3]);
+        loaded.flags.show_all = false;
+        assert_eq!(loaded.visible_indices(), [0, 1]);
+    }
+
+    #[test]
+    fn autosave_waits_for_changes_and_the_interval() {
+        let mut state = State::new();
+        state.autosave_interval = 60;
+        state.last_autosave = Instant::now() - Duration::from_secs(61);
+        assert!(!state.autosave_due());
+        add_user(&mut state, "A.1234", None, false);
+        assert!(state.dirty);
+        assert!(state.autosave_due());
+        state.last_autosave = Instant::now();
+        assert!(!state.autosave_due());
+        state.last_autosave = Instant::now() - Duration::from_secs(61);
+        state.autosave_interval = 0;
+        assert!(!state.autosave_due());
+    }
+
+    #[test]
+    fn sorted_indices_pinned_first_ignored_last() {
+        let mut players = player_list(&["D.1234", "C.1234", "B.1234", "A.1234"]);
+        players.upsert("C.1234").pinned = true;
+        players.upsert("A.1234").ignored = true;
+        let by_name = Sort { column: SortColumn::Name, ascending: true };
+        assert_eq!(players.sorted_indices(by_name), [1, 2, 0, 3]);
+        let descending = Sort { column: SortColumn::Name, ascending: false };
+        assert_eq!(players.sorted_indices(descending), [1, 0, 2, 3]);
+        assert_eq!(players.sorted_indices(Sort::new()), [1, 0, 2, 3]);
     }
 }